@@ -2,10 +2,8 @@ use anyhow::{Context, Result};
 use reqwest::multipart::{Form, Part};
 use serde::Deserialize;
 use std::fs;
-use std::time::Duration;
-use tokio::time::sleep;
 
-use super::{filename_for_upload, mime_type_for_image, Client, BASE_RETRY_DELAY_MS, MAX_RETRIES};
+use super::{filename_for_upload, mime_type_for_image, Client, PriceInformation, LIST_PAGE_SIZE};
 
 #[derive(Debug, Clone)]
 pub struct CreateDevProductRequest {
@@ -13,6 +11,8 @@ pub struct CreateDevProductRequest {
     pub price: u64,
     pub description: Option<String>,
     pub icon_path: Option<String>,
+    pub regional_pricing: Option<bool>,
+    pub store_page: Option<bool>,
 }
 
 #[derive(Debug, Clone)]
@@ -21,6 +21,9 @@ pub struct UpdateDevProductRequest {
     pub price: Option<u64>,
     pub description: Option<String>,
     pub icon_path: Option<String>,
+    pub is_for_sale: Option<bool>,
+    pub regional_pricing: Option<bool>,
+    pub store_page: Option<bool>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -29,6 +32,26 @@ pub struct DevProductResponse {
     pub product_id: u64,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DevProductDetails {
+    pub product_id: u64,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub is_for_sale: bool,
+    pub price_information: Option<PriceInformation>,
+    pub store_page_enabled: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct DevProductPage {
+    #[serde(default)]
+    developer_products: Vec<DevProductDetails>,
+    next_page_token: Option<String>,
+}
+
 fn build_create_form(request: &CreateDevProductRequest) -> Result<Form> {
     let mut form = Form::new()
         .text("name", request.name.clone())
@@ -37,6 +60,12 @@ fn build_create_form(request: &CreateDevProductRequest) -> Result<Form> {
     if let Some(ref desc) = request.description {
         form = form.text("description", desc.clone());
     }
+    if let Some(regional_pricing) = request.regional_pricing {
+        form = form.text("isRegionalPricingEnabled", regional_pricing.to_string());
+    }
+    if let Some(store_page) = request.store_page {
+        form = form.text("storePageEnabled", store_page.to_string());
+    }
 
     if let Some(ref icon_path) = request.icon_path {
         let icon_bytes = fs::read(icon_path)
@@ -64,6 +93,15 @@ fn build_update_form(request: &UpdateDevProductRequest) -> Result<Form> {
     if let Some(ref desc) = request.description {
         form = form.text("description", desc.clone());
     }
+    if let Some(is_for_sale) = request.is_for_sale {
+        form = form.text("isForSale", is_for_sale.to_string());
+    }
+    if let Some(regional_pricing) = request.regional_pricing {
+        form = form.text("isRegionalPricingEnabled", regional_pricing.to_string());
+    }
+    if let Some(store_page) = request.store_page {
+        form = form.text("storePageEnabled", store_page.to_string());
+    }
 
     if let Some(ref icon_path) = request.icon_path {
         let icon_bytes = fs::read(icon_path)
@@ -86,41 +124,24 @@ impl Client {
         request: CreateDevProductRequest,
     ) -> Result<DevProductResponse> {
         let url = format!(
-            "https://apis.roblox.com/developer-products/v2/universes/{}/developer-products",
+            "{}/developer-products/v2/universes/{}/developer-products",
+            self.base_url(),
             universe_id
         );
 
-        let mut retries = 0;
-        loop {
-            let form = build_create_form(&request)?;
-
-            let response = self
-                .http()
-                .post(&url)
-                .header("x-api-key", self.api_key())
-                .multipart(form)
-                .send()
-                .await?;
-
-            if response.status() == 429 && retries < MAX_RETRIES {
-                retries += 1;
-                let delay = Duration::from_millis(BASE_RETRY_DELAY_MS * (1 << retries));
-                eprintln!("  Rate limited, retrying in {:?}...", delay);
-                sleep(delay).await;
-                continue;
-            }
-
-            if !response.status().is_success() {
-                let status = response.status();
-                let text = response.text().await.unwrap_or_default();
-                anyhow::bail!("Failed to create dev product: {} - {}", status, text);
-            }
-
-            return response
-                .json()
-                .await
-                .context("Failed to parse dev product response");
-        }
+        let response = self
+            .execute("create dev product", false, || {
+                Ok(self
+                    .http()
+                    .post(&url)
+                    .multipart(build_create_form(&request)?))
+            })
+            .await?;
+
+        response
+            .json()
+            .await
+            .context("Failed to parse dev product response")
     }
 
     pub async fn update_dev_product(
@@ -130,37 +151,77 @@ impl Client {
         request: UpdateDevProductRequest,
     ) -> Result<()> {
         let url = format!(
-            "https://apis.roblox.com/developer-products/v2/universes/{}/developer-products/{}",
-            universe_id, product_id
+            "{}/developer-products/v2/universes/{}/developer-products/{}",
+            self.base_url(),
+            universe_id,
+            product_id
+        );
+
+        self.execute("update dev product", true, || {
+            Ok(self
+                .http()
+                .patch(&url)
+                .multipart(build_update_form(&request)?))
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_dev_product(
+        &self,
+        universe_id: u64,
+        product_id: u64,
+    ) -> Result<DevProductDetails> {
+        let url = format!(
+            "{}/developer-products/v2/universes/{}/developer-products/{}/creator",
+            self.base_url(),
+            universe_id,
+            product_id
+        );
+
+        let response = self
+            .execute("get dev product", true, || Ok(self.http().get(&url)))
+            .await?;
+
+        response
+            .json()
+            .await
+            .context("Failed to parse dev product details")
+    }
+
+    /// List every dev product in the universe, following pagination.
+    pub async fn list_dev_products(&self, universe_id: u64) -> Result<Vec<DevProductDetails>> {
+        let url = format!(
+            "{}/developer-products/v2/universes/{}/developer-products/creator",
+            self.base_url(),
+            universe_id
         );
 
-        let mut retries = 0;
+        let mut products = Vec::new();
+        let mut page_token: Option<String> = None;
         loop {
-            let form = build_update_form(&request)?;
+            let mut query = vec![("pageSize", LIST_PAGE_SIZE.to_string())];
+            if let Some(ref token) = page_token {
+                query.push(("pageToken", token.clone()));
+            }
 
             let response = self
-                .http()
-                .patch(&url)
-                .header("x-api-key", self.api_key())
-                .multipart(form)
-                .send()
+                .execute("list dev products", true, || {
+                    Ok(self.http().get(&url).query(&query))
+                })
                 .await?;
 
-            if response.status() == 429 && retries < MAX_RETRIES {
-                retries += 1;
-                let delay = Duration::from_millis(BASE_RETRY_DELAY_MS * (1 << retries));
-                eprintln!("  Rate limited, retrying in {:?}...", delay);
-                sleep(delay).await;
-                continue;
-            }
+            let page: DevProductPage = response
+                .json()
+                .await
+                .context("Failed to parse dev product list")?;
+            products.extend(page.developer_products);
 
-            if !response.status().is_success() {
-                let status = response.status();
-                let text = response.text().await.unwrap_or_default();
-                anyhow::bail!("Failed to update dev product: {} - {}", status, text);
+            match page.next_page_token.filter(|t| !t.is_empty()) {
+                Some(token) => page_token = Some(token),
+                None => return Ok(products),
             }
-
-            return Ok(());
         }
     }
 }
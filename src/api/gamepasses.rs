@@ -2,10 +2,8 @@ use anyhow::{Context, Result};
 use reqwest::multipart::{Form, Part};
 use serde::Deserialize;
 use std::fs;
-use std::time::Duration;
-use tokio::time::sleep;
 
-use super::{filename_for_upload, mime_type_for_image, Client, BASE_RETRY_DELAY_MS, MAX_RETRIES};
+use super::{filename_for_upload, mime_type_for_image, Client, PriceInformation, LIST_PAGE_SIZE};
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -13,6 +11,35 @@ pub struct GamepassResponse {
     pub game_pass_id: u64,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GamepassDetails {
+    pub game_pass_id: u64,
+    pub name: String,
+    pub description: Option<String>,
+    #[serde(default)]
+    pub is_for_sale: bool,
+    pub price_information: Option<PriceInformation>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct GamepassPage {
+    #[serde(default)]
+    game_passes: Vec<GamepassDetails>,
+    next_page_token: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct CreateGamepassRequest {
+    pub name: String,
+    pub price: u64,
+    pub description: Option<String>,
+    pub icon_path: Option<String>,
+    pub is_for_sale: bool,
+    pub regional_pricing: Option<bool>,
+}
+
 #[derive(Debug, Clone)]
 pub struct UpdateGamepassRequest {
     pub name: Option<String>,
@@ -20,25 +47,23 @@ pub struct UpdateGamepassRequest {
     pub description: Option<String>,
     pub icon_path: Option<String>,
     pub is_for_sale: Option<bool>,
+    pub regional_pricing: Option<bool>,
 }
 
-fn build_create_form(
-    name: &str,
-    price: u64,
-    description: &Option<String>,
-    icon_path: &Option<String>,
-    is_for_sale: bool,
-) -> Result<Form> {
+fn build_create_form(request: &CreateGamepassRequest) -> Result<Form> {
     let mut form = Form::new()
-        .text("name", name.to_string())
-        .text("price", price.to_string())
-        .text("isForSale", is_for_sale.to_string());
+        .text("name", request.name.clone())
+        .text("price", request.price.to_string())
+        .text("isForSale", request.is_for_sale.to_string());
 
-    if let Some(ref desc) = description {
+    if let Some(ref desc) = request.description {
         form = form.text("description", desc.clone());
     }
+    if let Some(regional_pricing) = request.regional_pricing {
+        form = form.text("isRegionalPricingEnabled", regional_pricing.to_string());
+    }
 
-    if let Some(ref icon_path) = icon_path {
+    if let Some(ref icon_path) = request.icon_path {
         let icon_bytes = fs::read(icon_path)
             .with_context(|| format!("Failed to read icon file: {}", icon_path))?;
         let mime_type = mime_type_for_image(icon_path);
@@ -67,6 +92,9 @@ fn build_update_form(request: &UpdateGamepassRequest) -> Result<Form> {
     if let Some(is_for_sale) = request.is_for_sale {
         form = form.text("isForSale", is_for_sale.to_string());
     }
+    if let Some(regional_pricing) = request.regional_pricing {
+        form = form.text("isRegionalPricingEnabled", regional_pricing.to_string());
+    }
 
     if let Some(ref icon_path) = request.icon_path {
         let icon_bytes = fs::read(icon_path)
@@ -86,48 +114,27 @@ impl Client {
     pub async fn create_gamepass(
         &self,
         universe_id: u64,
-        name: String,
-        price: u64,
-        description: Option<String>,
-        icon_path: Option<String>,
-        is_for_sale: bool,
+        request: CreateGamepassRequest,
     ) -> Result<GamepassResponse> {
         let url = format!(
-            "https://apis.roblox.com/game-passes/v1/universes/{}/game-passes",
+            "{}/game-passes/v1/universes/{}/game-passes",
+            self.base_url(),
             universe_id
         );
 
-        let mut retries = 0;
-        loop {
-            let form = build_create_form(&name, price, &description, &icon_path, is_for_sale)?;
-
-            let response = self
-                .http()
-                .post(&url)
-                .header("x-api-key", self.api_key())
-                .multipart(form)
-                .send()
-                .await?;
-
-            if response.status() == 429 && retries < MAX_RETRIES {
-                retries += 1;
-                let delay = Duration::from_millis(BASE_RETRY_DELAY_MS * (1 << retries));
-                eprintln!("  Rate limited, retrying in {:?}...", delay);
-                sleep(delay).await;
-                continue;
-            }
-
-            if !response.status().is_success() {
-                let status = response.status();
-                let text = response.text().await.unwrap_or_default();
-                anyhow::bail!("Failed to create gamepass: {} - {}", status, text);
-            }
-
-            return response
-                .json()
-                .await
-                .context("Failed to parse gamepass response");
-        }
+        let response = self
+            .execute("create gamepass", false, || {
+                Ok(self
+                    .http()
+                    .post(&url)
+                    .multipart(build_create_form(&request)?))
+            })
+            .await?;
+
+        response
+            .json()
+            .await
+            .context("Failed to parse gamepass response")
     }
 
     pub async fn update_gamepass(
@@ -137,37 +144,77 @@ impl Client {
         request: UpdateGamepassRequest,
     ) -> Result<()> {
         let url = format!(
-            "https://apis.roblox.com/game-passes/v1/universes/{}/game-passes/{}",
-            universe_id, gamepass_id
+            "{}/game-passes/v1/universes/{}/game-passes/{}",
+            self.base_url(),
+            universe_id,
+            gamepass_id
+        );
+
+        self.execute("update gamepass", true, || {
+            Ok(self
+                .http()
+                .patch(&url)
+                .multipart(build_update_form(&request)?))
+        })
+        .await?;
+
+        Ok(())
+    }
+
+    pub async fn get_gamepass(
+        &self,
+        universe_id: u64,
+        gamepass_id: u64,
+    ) -> Result<GamepassDetails> {
+        let url = format!(
+            "{}/game-passes/v1/universes/{}/game-passes/{}/creator",
+            self.base_url(),
+            universe_id,
+            gamepass_id
         );
 
-        let mut retries = 0;
+        let response = self
+            .execute("get gamepass", true, || Ok(self.http().get(&url)))
+            .await?;
+
+        response
+            .json()
+            .await
+            .context("Failed to parse gamepass details")
+    }
+
+    /// List every gamepass in the universe, following pagination.
+    pub async fn list_gamepasses(&self, universe_id: u64) -> Result<Vec<GamepassDetails>> {
+        let url = format!(
+            "{}/game-passes/v1/universes/{}/game-passes/creator",
+            self.base_url(),
+            universe_id
+        );
+
+        let mut gamepasses = Vec::new();
+        let mut page_token: Option<String> = None;
         loop {
-            let form = build_update_form(&request)?;
+            let mut query = vec![("pageSize", LIST_PAGE_SIZE.to_string())];
+            if let Some(ref token) = page_token {
+                query.push(("pageToken", token.clone()));
+            }
 
             let response = self
-                .http()
-                .patch(&url)
-                .header("x-api-key", self.api_key())
-                .multipart(form)
-                .send()
+                .execute("list gamepasses", true, || {
+                    Ok(self.http().get(&url).query(&query))
+                })
                 .await?;
 
-            if response.status() == 429 && retries < MAX_RETRIES {
-                retries += 1;
-                let delay = Duration::from_millis(BASE_RETRY_DELAY_MS * (1 << retries));
-                eprintln!("  Rate limited, retrying in {:?}...", delay);
-                sleep(delay).await;
-                continue;
-            }
+            let page: GamepassPage = response
+                .json()
+                .await
+                .context("Failed to parse gamepass list")?;
+            gamepasses.extend(page.game_passes);
 
-            if !response.status().is_success() {
-                let status = response.status();
-                let text = response.text().await.unwrap_or_default();
-                anyhow::bail!("Failed to update gamepass: {} - {}", status, text);
+            match page.next_page_token.filter(|t| !t.is_empty()) {
+                Some(token) => page_token = Some(token),
+                None => return Ok(gamepasses),
             }
-
-            return Ok(());
         }
     }
 }
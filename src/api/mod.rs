@@ -1,16 +1,24 @@
 mod dev_products;
 mod gamepasses;
+mod retry;
 
 pub use dev_products::*;
 pub use gamepasses::*;
+pub use retry::RetryPolicy;
 
 use anyhow::{Context, Result};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::Deserialize;
 use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::{sleep, sleep_until, Instant};
 
-/// Maximum number of retries on rate limit
-const MAX_RETRIES: u32 = 5;
-/// Base delay between retries (doubles each time)
-const BASE_RETRY_DELAY_MS: u64 = 500;
+/// Default Open Cloud base URL
+pub const DEFAULT_API_URL: &str = "https://apis.roblox.com";
+
+/// Number of items requested per page from list endpoints
+const LIST_PAGE_SIZE: u32 = 50;
 
 /// Detect MIME type from file extension
 pub fn mime_type_for_image(path: &str) -> &'static str {
@@ -38,19 +46,67 @@ pub fn filename_for_upload(path: &str) -> String {
         .to_string()
 }
 
+/// An unsuccessful response from the Open Cloud API.
+#[derive(Debug)]
+pub struct ApiError {
+    pub operation: &'static str,
+    pub status: StatusCode,
+    pub body: String,
+}
+
+impl std::fmt::Display for ApiError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "Failed to {}: {} - {}",
+            self.operation, self.status, self.body
+        )
+    }
+}
+
+impl std::error::Error for ApiError {}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PriceInformation {
+    pub default_price_in_robux: Option<u64>,
+    #[serde(default)]
+    pub enabled_features: Vec<String>,
+}
+
+impl PriceInformation {
+    /// Whether Roblox adjusts the price for each region
+    pub fn regional_pricing(&self) -> bool {
+        self.enabled_features.iter().any(|f| f == "RegionalPricing")
+    }
+}
+
 pub struct Client {
     http: reqwest::Client,
     api_key: String,
+    base_url: String,
+    retry: RetryPolicy,
+    /// Shared rate limit backoff, so concurrent requests pause together
+    /// instead of each retrying on its own schedule
+    backoff_until: Mutex<Option<Instant>>,
 }
 
 impl Client {
-    pub fn new() -> Result<Self> {
+    pub fn new(base_url: &str, retry: RetryPolicy) -> Result<Self> {
         let api_key = std::env::var("ROBLOX_PRODUCTS_API_KEY")
             .context("ROBLOX_PRODUCTS_API_KEY environment variable not set")?;
 
-        let http = reqwest::Client::builder().build()?;
+        let http = reqwest::Client::builder()
+            .timeout(retry.timeout())
+            .build()?;
 
-        Ok(Self { http, api_key })
+        Ok(Self {
+            http,
+            api_key,
+            base_url: base_url.trim_end_matches('/').to_string(),
+            retry,
+            backoff_until: Mutex::new(None),
+        })
     }
 
     pub fn api_key(&self) -> &str {
@@ -60,4 +116,113 @@ impl Client {
     pub fn http(&self) -> &reqwest::Client {
         &self.http
     }
+
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Send a request, retrying according to the retry policy.
+    ///
+    /// `build` is called for every attempt, since multipart bodies can't be
+    /// reused. Non-idempotent requests are only retried when they can't have
+    /// been applied: after transport errors where the connection was never
+    /// made, and after 429 or a 503 with `Retry-After`. That way a create is
+    /// never applied twice.
+    ///
+    /// Returns the first successful response, or an [`ApiError`] for the final
+    /// unsuccessful one.
+    pub async fn execute(
+        &self,
+        operation: &'static str,
+        idempotent: bool,
+        build: impl Fn() -> Result<RequestBuilder>,
+    ) -> Result<Response> {
+        let deadline = Instant::now() + self.retry.deadline();
+        let mut attempt = 0;
+
+        loop {
+            self.wait_for_backoff().await;
+            let request = build()?.header("x-api-key", self.api_key());
+
+            let (delay, reason, rate_limited) = match request.send().await {
+                Ok(response) if response.status().is_success() => return Ok(response),
+                Ok(response) => {
+                    let status = response.status();
+                    if attempt >= self.retry.max_retries
+                        || !self
+                            .retry
+                            .should_retry_status(status, idempotent, response.headers())
+                    {
+                        let body = response.text().await.unwrap_or_default();
+                        return Err(ApiError {
+                            operation,
+                            status,
+                            body,
+                        }
+                        .into());
+                    }
+                    (
+                        self.retry.delay(attempt + 1, Some(response.headers())),
+                        status.to_string(),
+                        status == StatusCode::TOO_MANY_REQUESTS,
+                    )
+                }
+                Err(e) => {
+                    let retryable = e.is_connect() || (idempotent && !e.is_builder());
+                    if attempt >= self.retry.max_retries || !retryable {
+                        return Err(e).with_context(|| format!("Failed to {}", operation));
+                    }
+                    (self.retry.delay(attempt + 1, None), e.to_string(), false)
+                }
+            };
+
+            if Instant::now() + delay > deadline {
+                anyhow::bail!(
+                    "Failed to {}: gave up after {} attempts ({})",
+                    operation,
+                    attempt + 1,
+                    reason
+                );
+            }
+
+            attempt += 1;
+            if rate_limited {
+                self.back_off(delay).await;
+            } else {
+                eprintln!("  {} ({}), retrying in {:?}...", operation, reason, delay);
+                sleep(delay).await;
+            }
+        }
+    }
+
+    /// Wait until any shared rate limit backoff has passed
+    async fn wait_for_backoff(&self) {
+        let until = *self.backoff_until.lock().unwrap();
+        if let Some(until) = until {
+            sleep_until(until).await;
+        }
+    }
+
+    /// Pause all requests after a rate limit response, then wait it out.
+    /// If another request already paused for longer, that pause is reused.
+    async fn back_off(&self, delay: Duration) {
+        let until = Instant::now() + delay;
+
+        let extended = {
+            let mut backoff_until = self.backoff_until.lock().unwrap();
+            match *backoff_until {
+                Some(existing) if existing >= until => false,
+                _ => {
+                    *backoff_until = Some(until);
+                    true
+                }
+            }
+        };
+
+        if extended {
+            eprintln!("  Rate limited, retrying in {:?}...", delay);
+        }
+
+        self.wait_for_backoff().await;
+    }
 }
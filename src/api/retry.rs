@@ -0,0 +1,211 @@
+use reqwest::header::HeaderMap;
+use reqwest::StatusCode;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How requests to Open Cloud are retried. Configured with the `[retry]`
+/// table in spearmint.toml.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[serde(default)]
+#[schemars(deny_unknown_fields)]
+pub struct RetryPolicy {
+    /// Maximum number of retries for a single request
+    pub max_retries: u32,
+    /// Delay before the first retry, doubling with each attempt
+    pub base_delay_ms: u64,
+    /// Upper bound for a computed backoff delay
+    pub max_delay_ms: u64,
+    /// Total time a single request may spend retrying before giving up
+    pub deadline_secs: u64,
+    /// Timeout for each individual HTTP request
+    pub timeout_secs: u64,
+    /// HTTP status codes that are retried
+    pub retry_statuses: Vec<u16>,
+    /// Randomise delays so concurrent requests don't retry in lockstep
+    pub jitter: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_retries: 5,
+            base_delay_ms: 500,
+            max_delay_ms: 30_000,
+            deadline_secs: 120,
+            timeout_secs: 60,
+            retry_statuses: vec![429, 500, 502, 503, 504],
+            jitter: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Whether a failed response should be retried. The server may have
+    /// already applied a non-idempotent request that failed with a server
+    /// error, so those are only retried when it was never processed: on 429,
+    /// or on 503 with `Retry-After`.
+    pub fn should_retry_status(
+        &self,
+        status: StatusCode,
+        idempotent: bool,
+        headers: &HeaderMap,
+    ) -> bool {
+        if !self.retry_statuses.contains(&status.as_u16()) {
+            return false;
+        }
+
+        idempotent
+            || status == StatusCode::TOO_MANY_REQUESTS
+            || (status == StatusCode::SERVICE_UNAVAILABLE && headers.contains_key("retry-after"))
+    }
+
+    pub fn deadline(&self) -> Duration {
+        Duration::from_secs(self.deadline_secs)
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(self.timeout_secs)
+    }
+
+    /// Delay before retry number `attempt` (starting at 1). A delay requested
+    /// by the server through response headers takes precedence over backoff.
+    pub fn delay(&self, attempt: u32, headers: Option<&HeaderMap>) -> Duration {
+        if let Some(delay) = headers.and_then(header_delay) {
+            // Spread retries slightly so waiting requests don't all fire at once
+            let spread = if self.jitter {
+                fastrand::u64(0..250)
+            } else {
+                0
+            };
+            return delay + Duration::from_millis(spread);
+        }
+
+        let exponent = attempt.saturating_sub(1).min(31);
+        let backoff = self
+            .base_delay_ms
+            .saturating_mul(1 << exponent)
+            .min(self.max_delay_ms);
+
+        // Equal jitter: keep at least half of the backoff
+        let backoff = if self.jitter && backoff > 1 {
+            backoff / 2 + fastrand::u64(0..=backoff / 2)
+        } else {
+            backoff
+        };
+
+        Duration::from_millis(backoff)
+    }
+}
+
+/// Read the delay requested by `Retry-After` or the rate limit reset header.
+fn header_delay(headers: &HeaderMap) -> Option<Duration> {
+    ["retry-after", "x-ratelimit-reset"]
+        .iter()
+        .find_map(|name| {
+            let value = headers.get(*name)?.to_str().ok()?.trim();
+            let seconds: f64 = value.parse().ok()?;
+            (0.0..=86_400.0)
+                .contains(&seconds)
+                .then(|| Duration::from_secs_f64(seconds))
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn policy(jitter: bool) -> RetryPolicy {
+        RetryPolicy {
+            base_delay_ms: 100,
+            max_delay_ms: 1_000,
+            jitter,
+            ..RetryPolicy::default()
+        }
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn header_delay_prefers_retry_after() {
+        let both = headers(&[("retry-after", "2"), ("x-ratelimit-reset", "5")]);
+        assert_eq!(header_delay(&both), Some(Duration::from_secs(2)));
+
+        let reset = headers(&[("x-ratelimit-reset", "1.5")]);
+        assert_eq!(header_delay(&reset), Some(Duration::from_millis(1_500)));
+    }
+
+    #[test]
+    fn header_delay_ignores_invalid_values() {
+        assert_eq!(header_delay(&HeaderMap::new()), None);
+        assert_eq!(header_delay(&headers(&[("retry-after", "soon")])), None);
+        assert_eq!(header_delay(&headers(&[("retry-after", "-1")])), None);
+        assert_eq!(header_delay(&headers(&[("retry-after", "100000")])), None);
+
+        // An unusable Retry-After falls back to the reset header
+        let fallback = headers(&[("retry-after", "soon"), ("x-ratelimit-reset", "3")]);
+        assert_eq!(header_delay(&fallback), Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn delay_doubles_up_to_the_cap() {
+        let policy = policy(false);
+        let delays: Vec<u128> = (1..=6)
+            .map(|attempt| policy.delay(attempt, None).as_millis())
+            .collect();
+        assert_eq!(delays, [100, 200, 400, 800, 1_000, 1_000]);
+        assert_eq!(policy.delay(u32::MAX, None), Duration::from_millis(1_000));
+    }
+
+    #[test]
+    fn jitter_keeps_at_least_half_the_backoff() {
+        let policy = policy(true);
+        for _ in 0..100 {
+            let delay = policy.delay(3, None).as_millis();
+            assert!((200..=400).contains(&delay), "{}", delay);
+            let capped = policy.delay(10, None).as_millis();
+            assert!((500..=1_000).contains(&capped), "{}", capped);
+        }
+    }
+
+    #[test]
+    fn header_delay_overrides_backoff() {
+        let retry_after = headers(&[("retry-after", "3")]);
+        assert_eq!(
+            policy(false).delay(1, Some(&retry_after)),
+            Duration::from_secs(3)
+        );
+
+        // Not capped by max_delay_ms, and only spread slightly
+        for _ in 0..100 {
+            let delay = policy(true).delay(1, Some(&retry_after)).as_millis();
+            assert!((3_000..3_250).contains(&delay), "{}", delay);
+        }
+    }
+
+    #[test]
+    fn non_idempotent_requests_retry_only_when_unprocessed() {
+        let policy = RetryPolicy::default();
+        let none = HeaderMap::new();
+        let retry_after = headers(&[("retry-after", "1")]);
+
+        assert!(policy.should_retry_status(StatusCode::INTERNAL_SERVER_ERROR, true, &none));
+        assert!(!policy.should_retry_status(StatusCode::BAD_REQUEST, true, &none));
+
+        assert!(policy.should_retry_status(StatusCode::TOO_MANY_REQUESTS, false, &none));
+        assert!(policy.should_retry_status(StatusCode::SERVICE_UNAVAILABLE, false, &retry_after));
+        assert!(!policy.should_retry_status(StatusCode::SERVICE_UNAVAILABLE, false, &none));
+        assert!(!policy.should_retry_status(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            false,
+            &retry_after
+        ));
+        assert!(!policy.should_retry_status(StatusCode::GATEWAY_TIMEOUT, false, &none));
+    }
+}
@@ -1,11 +1,25 @@
-use anyhow::Result;
-use clap::{Parser, Subcommand};
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
+use std::fs;
+use std::io::Write;
 use std::path::Path;
 
 use crate::api::Client;
 use crate::codegen;
-use crate::config::{self, DEFAULT_CONFIG_PATH};
-use crate::sync::{self, DEFAULT_MAPPING_PATH};
+use crate::config::{
+    self, Config, ConfigEditor, OrphanConfig, OrphanPolicy, DEFAULT_CONFIG_PATH,
+    DEFAULT_SCHEMA_PATH,
+};
+use crate::drift;
+use crate::import;
+use crate::migrate;
+use crate::plan;
+use crate::report::SyncReport;
+use crate::sync::{self, Mapping, DEFAULT_MAPPING_PATH};
+use crate::validate;
+
+/// Default number of products synced concurrently
+const DEFAULT_JOBS: usize = 4;
 
 #[derive(Parser)]
 #[command(name = "spearmint")]
@@ -25,40 +39,109 @@ pub enum Commands {
         force: bool,
     },
     /// Sync all products to Roblox (create/update)
-    Sync {
-        /// Config file path
-        #[arg(short, long, default_value = DEFAULT_CONFIG_PATH)]
-        config: String,
-        /// Mapping file path
-        #[arg(short, long, default_value = DEFAULT_MAPPING_PATH)]
-        mapping: String,
-        /// Skip code generation after sync
-        #[arg(long = "no-generate", action = clap::ArgAction::SetFalse)]
-        generate: bool,
-        /// Force re-sync all products, ignoring lock file
+    Sync(SyncArgs),
+    /// Show what sync would do without making any API calls
+    Plan {
+        #[command(flatten)]
+        project: ProjectArgs,
+        /// Plan a forced re-sync of all products, ignoring lock file
         #[arg(short, long)]
         force: bool,
+        /// What to do with products removed from the config (overrides [orphans] policy)
+        #[arg(long, value_enum)]
+        orphans: Option<OrphanPolicy>,
+    },
+    /// Compare synced products against their live state on Roblox
+    Drift {
+        #[command(flatten)]
+        project: ProjectArgs,
+    },
+    /// Import existing products from the universe into the config and lock file
+    Import {
+        #[command(flatten)]
+        project: ProjectArgs,
+    },
+    /// Rename a product key without creating a new product on Roblox
+    Rename {
+        /// Current product key
+        old: String,
+        /// New product key
+        new: String,
+        #[command(flatten)]
+        project: ProjectArgs,
     },
-    /// Generate Lua and TypeScript output without syncing
+    /// Upgrade the config and lock file to the current format
+    Migrate {
+        #[command(flatten)]
+        project: ProjectArgs,
+    },
+    /// Check products against Roblox limits without syncing
+    Validate {
+        #[command(flatten)]
+        project: ProjectArgs,
+    },
+    /// Generate every configured output without syncing
     Generate {
-        /// Config file path
-        #[arg(short, long, default_value = DEFAULT_CONFIG_PATH)]
-        config: String,
-        /// Mapping file path
-        #[arg(short, long, default_value = DEFAULT_MAPPING_PATH)]
-        mapping: String,
+        #[command(flatten)]
+        project: ProjectArgs,
+    },
+    /// Print the JSON Schema for spearmint.toml
+    Schema {
+        /// Write the schema to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<String>,
     },
     /// List current products and their status
     List {
-        /// Config file path
-        #[arg(short, long, default_value = DEFAULT_CONFIG_PATH)]
-        config: String,
-        /// Mapping file path
-        #[arg(short, long, default_value = DEFAULT_MAPPING_PATH)]
-        mapping: String,
+        #[command(flatten)]
+        project: ProjectArgs,
     },
 }
 
+/// Which config, environment and lock file a command works on.
+#[derive(Args)]
+pub struct ProjectArgs {
+    /// Config file path
+    #[arg(short, long, default_value = DEFAULT_CONFIG_PATH)]
+    pub config: String,
+    /// Mapping file path [default: the environment's lock file, or spearmint.lock.toml]
+    #[arg(short, long)]
+    pub mapping: Option<String>,
+    /// Environment from [environments] to use
+    #[arg(short, long)]
+    pub env: Option<String>,
+}
+
+#[derive(Args)]
+pub struct SyncArgs {
+    #[command(flatten)]
+    pub project: ProjectArgs,
+    /// Skip code generation after sync
+    #[arg(long = "no-generate", action = clap::ArgAction::SetFalse)]
+    pub generate: bool,
+    /// Force re-sync all products, ignoring lock file
+    #[arg(short, long)]
+    pub force: bool,
+    /// Show what would be synced without making any API calls
+    #[arg(long, conflicts_with = "refresh")]
+    pub dry_run: bool,
+    /// Refresh the lock file from the live Roblox state before syncing
+    #[arg(long)]
+    pub refresh: bool,
+    /// What to do with products removed from the config (overrides [orphans] policy)
+    #[arg(long, value_enum)]
+    pub orphans: Option<OrphanPolicy>,
+    /// Number of products to sync at once
+    #[arg(short, long, default_value_t = DEFAULT_JOBS)]
+    pub jobs: usize,
+    /// Write a JSON report of the sync to this path
+    #[arg(long)]
+    pub report: Option<String>,
+    /// Add a Markdown summary to the GitHub Actions job summary
+    #[arg(long)]
+    pub summary: bool,
+}
+
 pub fn init(force: bool) -> Result<()> {
     let config_path = Path::new(DEFAULT_CONFIG_PATH);
 
@@ -69,10 +152,16 @@ pub fn init(force: bool) -> Result<()> {
         );
     }
 
-    let config = config::create_default();
-    config::save(&config, DEFAULT_CONFIG_PATH)?;
+    // Point editors at the schema so they can complete and check the config
+    let config = toml::to_string_pretty(&config::create_default())?;
+    fs::write(
+        config_path,
+        format!("#:schema ./{}\n\n{}", DEFAULT_SCHEMA_PATH, config),
+    )?;
+    fs::write(DEFAULT_SCHEMA_PATH, config::json_schema()?)?;
 
     println!("Created config file: {}", config_path.display());
+    println!("Created schema file: {}", DEFAULT_SCHEMA_PATH);
     println!("\nNext steps:");
     println!("1. Edit spearmint.toml with your universe ID and products");
     println!("2. Set ROBLOX_PRODUCTS_API_KEY in your .env file");
@@ -81,63 +170,325 @@ pub fn init(force: bool) -> Result<()> {
     Ok(())
 }
 
-pub async fn sync(
-    config_path: String,
-    mapping_path: String,
-    generate: bool,
-    force: bool,
-) -> Result<()> {
-    let config = config::load(&config_path)?;
-    let mut mapping = sync::load_mapping(&mapping_path)?;
-    let client = Client::new()?;
+pub async fn sync(args: SyncArgs) -> Result<()> {
+    let (config, mut mapping, mapping_path) = load_project(&args.project)?;
+    check_config(&config, &args.project)?;
+    let client = Client::new(&config.api_url(), config.retry.clone().unwrap_or_default())?;
+
+    if args.refresh {
+        println!("Refreshing lock file from Roblox...\n");
+        let drift_report = drift::detect(&client, &config, &mapping).await;
+        drift_report.print();
+        println!();
 
-    if force {
+        if !drift_report.errors.is_empty() {
+            anyhow::bail!(
+                "Failed to refresh {} product(s) from Roblox",
+                drift_report.errors.len()
+            );
+        }
+        drift::refresh_mapping(&drift_report, &mut mapping);
+    }
+
+    if args.force {
         println!("Force sync enabled - re-syncing all products...\n");
     }
     println!("Syncing products for universe {}...\n", config.universe_id);
 
-    let results = sync::sync_all_products(&client, &config, &mut mapping, force).await?;
+    let orphans = orphan_config(&config, args.orphans);
+    let plan = plan::build(&config, &mapping, args.force, &orphans);
+    let report =
+        sync::sync_all_products(&client, &plan, &mut mapping, &mapping_path, args.jobs).await?;
 
     sync::save_mapping(&mapping, &mapping_path)?;
     println!("\nMapping saved to: {}", mapping_path);
 
-    if generate {
+    if args.generate {
         codegen::write_output(&config, &mapping)?;
     }
 
-    let created = results.iter().filter(|r| r.action == "created").count();
-    let updated = results.iter().filter(|r| r.action == "updated").count();
-    let skipped = results
-        .iter()
-        .filter(|r| r.action == "skipped" && r.error.is_none())
-        .count();
-    let failed = results.iter().filter(|r| r.error.is_some()).count();
+    report.print_summary();
+
+    if let Some(ref report_path) = args.report {
+        report.write_json(report_path)?;
+        println!("Report saved to: {}", report_path);
+    }
+
+    // The products are already synced, so a missing summary isn't a failure
+    if args.summary {
+        if let Err(e) = write_step_summary(&report) {
+            eprintln!("Warning: failed to write job summary: {:#}", e);
+        }
+    }
+
+    if report.failed() > 0 {
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Append the sync report to the GitHub Actions job summary.
+fn write_step_summary(report: &SyncReport) -> Result<()> {
+    let summary_path = std::env::var("GITHUB_STEP_SUMMARY")
+        .context("GITHUB_STEP_SUMMARY is not set; --summary only works in GitHub Actions")?;
+    let mut file = fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&summary_path)
+        .with_context(|| format!("Failed to open {}", summary_path))?;
+    file.write_all(report.to_markdown().as_bytes())?;
+    Ok(())
+}
+
+/// Load the config for the selected environment and its lock file, following
+/// any product renames. Also returns the lock file path.
+fn load_project(project: &ProjectArgs) -> Result<(Config, Mapping, String)> {
+    let mut config = config::load(&project.config)?;
+    let mapping_path = mapping_path(&config, project);
+
+    if let Some(ref name) = project.env {
+        config = config.for_environment(name)?;
+    }
+
+    let mut mapping = sync::load_mapping(&mapping_path)?;
+
+    for (old, new) in sync::apply_renames(&config, &mut mapping) {
+        println!("Renamed lock entry \"{}\" to \"{}\"", old, new);
+    }
+
+    Ok((config, mapping, mapping_path))
+}
+
+/// The lock file for the selected environment, unless --mapping overrides it.
+fn mapping_path(config: &Config, project: &ProjectArgs) -> String {
+    if let Some(ref path) = project.mapping {
+        return path.clone();
+    }
+    match project.env {
+        Some(ref name) => config
+            .environments
+            .get(name)
+            .map(|env| env.lock_path(name))
+            .unwrap_or_else(|| DEFAULT_MAPPING_PATH.to_string()),
+        None => DEFAULT_MAPPING_PATH.to_string(),
+    }
+}
+
+/// Print any validation problems and fail if there were some.
+fn check_config(config: &Config, project: &ProjectArgs) -> Result<()> {
+    let diagnostics = validate::check(config, &project.config, project.env.as_deref())?;
+
+    for diagnostic in &diagnostics {
+        eprintln!("{}", diagnostic);
+    }
+    if !diagnostics.is_empty() {
+        anyhow::bail!(
+            "Found {} problem(s) in {}",
+            diagnostics.len(),
+            project.config
+        );
+    }
+
+    Ok(())
+}
+
+/// Resolve the orphan settings from the config, with an optional CLI override.
+fn orphan_config(config: &Config, policy: Option<OrphanPolicy>) -> OrphanConfig {
+    let mut orphans = config.orphans.clone().unwrap_or_default();
+    if let Some(policy) = policy {
+        orphans.policy = policy;
+    }
+    orphans
+}
+
+/// Print the sync plan, exiting with status 2 if there are pending changes.
+pub fn plan(project: ProjectArgs, force: bool, orphans: Option<OrphanPolicy>) -> Result<()> {
+    let (config, mapping, _) = load_project(&project)?;
+    // A dry run fails on the same problems a real sync would
+    check_config(&config, &project)?;
+
+    let orphans = orphan_config(&config, orphans);
+    let plan = plan::build(&config, &mapping, force, &orphans);
+    plan.print();
+
+    if plan.has_changes() {
+        std::process::exit(2);
+    }
+
+    Ok(())
+}
+
+/// Report products whose live state differs from the config or lock file.
+///
+/// Exits with status 2 if any drift is found.
+pub async fn drift(project: ProjectArgs) -> Result<()> {
+    let (config, mapping, _) = load_project(&project)?;
+    let client = Client::new(&config.api_url(), config.retry.clone().unwrap_or_default())?;
 
     println!(
-        "\nSummary: {} created, {} updated, {} unchanged, {} failed",
-        created, updated, skipped, failed
+        "Checking products for universe {} against Roblox...\n",
+        config.universe_id
     );
 
-    if failed > 0 {
+    let report = drift::detect(&client, &config, &mapping).await;
+    report.print();
+
+    if !report.errors.is_empty() {
         std::process::exit(1);
     }
+    if report.has_drift() {
+        std::process::exit(2);
+    }
+
+    Ok(())
+}
+
+pub async fn import(project: ProjectArgs) -> Result<()> {
+    let (config, mut mapping, mapping_path) = load_project(&project)?;
+    let client = Client::new(&config.api_url(), config.retry.clone().unwrap_or_default())?;
+
+    println!(
+        "Importing products from universe {}...\n",
+        config.universe_id
+    );
+
+    let imported = import::fetch_untracked(&client, &config, &mapping).await?;
+
+    if imported.is_empty() {
+        println!("No untracked products found.");
+        return Ok(());
+    }
+
+    for item in &imported {
+        println!(
+            "[imported] {} - {} (ID: {})",
+            item.product.product_type, item.key, item.entry.roblox_id
+        );
+    }
+    let count = imported.len();
+
+    // Imported products are added to the shared product list, without any
+    // environment overrides applied
+    let mut editor = ConfigEditor::new(&project.config);
+    for item in imported {
+        editor.add_product(&item.key, &item.product)?;
+        mapping.insert(item.key, item.entry);
+    }
+
+    editor.save()?;
+    sync::save_mapping(&mapping, &mapping_path)?;
+
+    println!("\nImported {} product(s) into {}", count, project.config);
+    println!("Mapping saved to: {}", mapping_path);
+
+    Ok(())
+}
+
+/// Rename a product key in the config and move its lock entry along with it.
+///
+/// The old key is recorded in `previous_keys`, so other lock files pick up the
+/// rename on their next sync.
+pub fn rename(old: String, new: String, project: ProjectArgs) -> Result<()> {
+    let (_, mut mapping, mapping_path) = load_project(&project)?;
+    let config = config::load(&project.config)?;
+
+    if config.products.contains_key(&new) {
+        anyhow::bail!("Product \"{}\" already exists", new);
+    }
+    // An orphaned entry under the new key still tracks a product on Roblox
+    if let Some(entry) = mapping.get(&new) {
+        anyhow::bail!(
+            "Product \"{}\" (ID: {}) already exists in {}",
+            new,
+            entry.roblox_id,
+            mapping_path
+        );
+    }
+
+    let mut editor = ConfigEditor::new(&project.config);
+    editor.rename_product(&config, &old, &new)?;
+    editor.save()?;
+    println!("Renamed \"{}\" to \"{}\" in {}", old, new, project.config);
+
+    if let Some(entry) = mapping.remove(&old) {
+        mapping.insert(new.clone(), entry);
+        sync::save_mapping(&mapping, &mapping_path)?;
+        println!("Mapping saved to: {}", mapping_path);
+    }
 
     Ok(())
 }
 
-pub fn generate(config_path: String, mapping_path: String) -> Result<()> {
-    let config = config::load(&config_path)?;
-    let mapping = sync::load_mapping(&mapping_path)?;
+/// Upgrade the config and the selected lock file to the current format.
+pub fn migrate(project: ProjectArgs) -> Result<()> {
+    match migrate::migrate_config(&project.config)? {
+        Some(from) => println!(
+            "Upgraded {} from version {} to {}",
+            project.config,
+            from,
+            config::CONFIG_VERSION
+        ),
+        None => println!("{} is already up to date", project.config),
+    }
+
+    let config = config::load(&project.config)?;
+    let mapping_path = mapping_path(&config, &project);
+    if !Path::new(&mapping_path).exists() {
+        return Ok(());
+    }
+
+    match migrate::migrate_lock(&mapping_path)? {
+        Some(from) => println!(
+            "Upgraded {} from version {} to {}",
+            mapping_path,
+            from,
+            sync::LOCK_VERSION
+        ),
+        None => println!("{} is already up to date", mapping_path),
+    }
+
+    Ok(())
+}
+
+/// Check the config against Roblox limits, exiting with status 1 on problems.
+pub fn validate(project: ProjectArgs) -> Result<()> {
+    let (config, _, _) = load_project(&project)?;
+
+    check_config(&config, &project)?;
+    println!("{} product(s) OK", config.products.len());
+
+    Ok(())
+}
+
+pub fn generate(project: ProjectArgs) -> Result<()> {
+    let (config, mapping, _) = load_project(&project)?;
 
     codegen::write_output(&config, &mapping)?;
 
     Ok(())
 }
 
-pub fn list(config_path: String, mapping_path: String) -> Result<()> {
-    let config = config::load(&config_path)?;
-    let mapping = sync::load_mapping(&mapping_path)?;
+pub fn schema(output: Option<String>) -> Result<()> {
+    let schema = config::json_schema()?;
 
+    match output {
+        Some(path) => {
+            fs::write(&path, schema)?;
+            println!("Schema saved to: {}", path);
+        }
+        None => println!("{}", schema),
+    }
+
+    Ok(())
+}
+
+pub fn list(project: ProjectArgs) -> Result<()> {
+    let (config, mapping, _) = load_project(&project)?;
+
+    if let Some(ref env) = project.env {
+        println!("Environment: {}", env);
+    }
     println!("Universe ID: {}", config.universe_id);
     println!("\nProducts:");
     println!("{}", "-".repeat(60));
@@ -161,7 +512,10 @@ pub fn list(config_path: String, mapping_path: String) -> Result<()> {
         println!("  {}", key);
         println!("    Type: {}", product.product_type);
         println!("    Name: {}", product.name);
-        println!("    Price: {} Robux", product.price);
+        match product.price_tier {
+            Some(ref tier) => println!("    Price: {} Robux ({})", product.price, tier),
+            None => println!("    Price: {} Robux", product.price),
+        }
         println!("    Status: {}", status);
         println!();
     }
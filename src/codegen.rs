@@ -1,31 +1,44 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use minijinja::{Environment, UndefinedBehavior};
+use serde::Serialize;
+use std::collections::HashSet;
 use std::fs;
 use std::path::Path;
 
-use crate::config::{Config, ProductType};
+use crate::config::{Config, OutputConfig, OutputFormat, OutputMode, Product, ProductType};
 use crate::sync::Mapping;
 
 pub fn write_output(config: &Config, mapping: &Mapping) -> Result<()> {
-    let output = match &config.output {
-        Some(o) => o,
-        None => {
-            println!("No output configured, skipping code generation");
-            return Ok(());
+    if config.output.is_empty() {
+        println!("No output configured, skipping code generation");
+        return Ok(());
+    }
+
+    for (name, output) in &config.output {
+        let content = match (&output.template, output.format) {
+            (Some(template), _) => render_template(template, name, config, mapping, output)?,
+            (None, OutputFormat::Luau) => generate_lua(config, mapping, output),
+            (None, OutputFormat::Json) => generate_json(config, mapping, output)?,
+        };
+        write_file(&output.path, &content)?;
+        match output.format {
+            OutputFormat::Luau => println!("Generated Lua output ({}): {}", name, output.path),
+            OutputFormat::Json => println!("Generated JSON output ({}): {}", name, output.path),
         }
-    };
 
-    let lua_content = generate_lua(config, mapping);
-    write_file(&output.path, &lua_content)?;
-    println!("Generated Lua output: {}", output.path);
-
-    if output.typescript {
-        let dts_path = output
-            .path
-            .replace(".luau", ".d.ts")
-            .replace(".lua", ".d.ts");
-        let dts_content = generate_dts(config, mapping);
-        write_file(&dts_path, &dts_content)?;
-        println!("Generated TypeScript definitions: {}", dts_path);
+        if output.typescript {
+            let dts_path = output
+                .path
+                .replace(".luau", ".d.ts")
+                .replace(".lua", ".d.ts");
+            let dts_content = match output.typescript_template {
+                Some(ref template) => render_template(template, name, config, mapping, output)?,
+                None => generate_dts(config, mapping, output),
+            };
+            write_file(&dts_path, &dts_content)?;
+            println!("Generated TypeScript definitions ({}): {}", name, dts_path);
+        }
     }
 
     Ok(())
@@ -42,45 +55,92 @@ fn write_file(path: &str, content: &str) -> Result<()> {
     Ok(())
 }
 
-fn generate_lua(config: &Config, mapping: &Mapping) -> String {
-    let mut dev_products = Vec::new();
-    let mut gamepasses = Vec::new();
+/// A product with a Roblox ID, ready to be written out.
+struct Entry<'a> {
+    key: &'a str,
+    id: u64,
+    product: &'a Product,
+}
 
-    for (key, product) in &config.products {
-        let roblox_id = product
-            .product_id
-            .or_else(|| mapping.get(key).map(|m| m.roblox_id));
+/// Products that have a Roblox ID, from the config or the lock file, and pass
+/// the output's filter, in config order.
+fn synced<'a>(config: &'a Config, mapping: &Mapping, output: &OutputConfig) -> Vec<Entry<'a>> {
+    config
+        .products
+        .iter()
+        .filter(|(key, product)| output.filter.matches(key, &product.product_type))
+        .filter_map(|(key, product)| {
+            let id = product
+                .product_id
+                .or_else(|| mapping.get(key).map(|m| m.roblox_id))?;
+            Some(Entry { key, id, product })
+        })
+        .collect()
+}
 
-        if let Some(id) = roblox_id {
-            match product.product_type {
-                ProductType::DevProduct => {
-                    dev_products.push((key.clone(), id));
-                }
-                ProductType::Gamepass => {
-                    gamepasses.push((key.clone(), id));
-                }
-            }
-        }
-    }
+/// Synced products, split into dev products and gamepasses.
+fn entries<'a>(
+    config: &'a Config,
+    mapping: &Mapping,
+    output: &OutputConfig,
+) -> (Vec<Entry<'a>>, Vec<Entry<'a>>) {
+    synced(config, mapping, output)
+        .into_iter()
+        .partition(|entry| entry.product.product_type == ProductType::DevProduct)
+}
 
-    dev_products.sort_by(|a, b| a.0.cmp(&b.0));
-    gamepasses.sort_by(|a, b| a.0.cmp(&b.0));
+fn generate_lua(config: &Config, mapping: &Mapping, output_config: &OutputConfig) -> String {
+    let (dev_products, gamepasses) = entries(config, mapping, output_config);
+    let mode = output_config.mode;
 
     let mut output = String::new();
+    output.push_str("--!strict\n");
     output.push_str("-- This file is auto-generated by spearmint. Do not edit manually.\n\n");
-    output.push_str("local Products = {\n");
+    output.push_str(&lua_types(&dev_products, &gamepasses, mode));
+    output.push_str("local Products: Products = {\n");
 
-    output.push_str("\tDevProducts = {\n");
-    for (key, id) in &dev_products {
-        output.push_str(&format!("\t\t[\"{}\"] = {},\n", key, id));
+    for (name, entries) in [("DevProducts", &dev_products), ("Gamepasses", &gamepasses)] {
+        output.push_str(&format!("\t{} = {{\n", name));
+        for entry in entries {
+            match mode {
+                OutputMode::Ids => {
+                    output.push_str(&format!(
+                        "\t\t[{}] = {},\n",
+                        lua_string(entry.key),
+                        entry.id
+                    ));
+                }
+                OutputMode::Metadata => {
+                    let product = entry.product;
+                    output.push_str(&format!("\t\t[{}] = {{\n", lua_string(entry.key)));
+                    output.push_str(&format!("\t\t\tid = {},\n", entry.id));
+                    output.push_str(&format!("\t\t\ttype = \"{}\",\n", product.product_type));
+                    output.push_str(&format!("\t\t\tname = {},\n", lua_string(&product.name)));
+                    output.push_str(&format!("\t\t\tprice = {},\n", product.price));
+                    if let Some(ref description) = product.description {
+                        output.push_str(&format!(
+                            "\t\t\tdescription = {},\n",
+                            lua_string(description)
+                        ));
+                    }
+                    output.push_str(&format!("\t\t\toffsale = {},\n", product.offsale));
+                    output.push_str("\t\t},\n");
+                }
+            }
+        }
+        output.push_str("\t},\n");
     }
-    output.push_str("\t},\n");
 
-    output.push_str("\tGamepasses = {\n");
-    for (key, id) in &gamepasses {
-        output.push_str(&format!("\t\t[\"{}\"] = {},\n", key, id));
+    for (name, entries) in [
+        ("DevProductsById", &dev_products),
+        ("GamepassesById", &gamepasses),
+    ] {
+        output.push_str(&format!("\t{} = {{\n", name));
+        for (id, key) in by_id(entries) {
+            output.push_str(&format!("\t\t[{}] = {},\n", id, lua_string(key)));
+        }
+        output.push_str("\t},\n");
     }
-    output.push_str("\t},\n");
 
     output.push_str("}\n\n");
     output.push_str("return Products\n");
@@ -88,44 +148,117 @@ fn generate_lua(config: &Config, mapping: &Mapping) -> String {
     output
 }
 
-fn generate_dts(config: &Config, mapping: &Mapping) -> String {
-    let mut dev_products = Vec::new();
-    let mut gamepasses = Vec::new();
+/// Key unions and a sealed table type for the module, so misspelled keys fail
+/// type checking.
+fn lua_types(dev_products: &[Entry], gamepasses: &[Entry], mode: OutputMode) -> String {
+    let value_type = match mode {
+        OutputMode::Ids => "number",
+        OutputMode::Metadata => "ProductInfo",
+    };
 
-    for (key, product) in &config.products {
-        let roblox_id = product
-            .product_id
-            .or_else(|| mapping.get(key).map(|m| m.roblox_id));
+    let mut output = String::new();
+    for (name, entries) in [("DevProductKey", dev_products), ("GamepassKey", gamepasses)] {
+        let keys: Vec<String> = entries.iter().map(|e| lua_string(e.key)).collect();
+        let union = if keys.is_empty() {
+            "never".to_string()
+        } else {
+            keys.join(" | ")
+        };
+        output.push_str(&format!("export type {} = {}\n", name, union));
+    }
+    output.push('\n');
 
-        if let Some(id) = roblox_id {
-            match product.product_type {
-                ProductType::DevProduct => {
-                    dev_products.push((key.clone(), id));
-                }
-                ProductType::Gamepass => {
-                    gamepasses.push((key.clone(), id));
-                }
-            }
+    if mode == OutputMode::Metadata {
+        output.push_str("export type ProductInfo = {\n");
+        output.push_str("\tid: number,\n");
+        output.push_str("\ttype: \"DevProduct\" | \"Gamepass\",\n");
+        output.push_str("\tname: string,\n");
+        output.push_str("\tprice: number,\n");
+        output.push_str("\tdescription: string?,\n");
+        output.push_str("\toffsale: boolean,\n");
+        output.push_str("}\n\n");
+    }
+
+    output.push_str("export type Products = {\n");
+    for (name, entries) in [("DevProducts", dev_products), ("Gamepasses", gamepasses)] {
+        output.push_str(&format!("\t{}: {{\n", name));
+        for entry in entries {
+            output.push_str(&format!(
+                "\t\t[{}]: {},\n",
+                lua_string(entry.key),
+                value_type
+            ));
         }
+        output.push_str("\t},\n");
     }
+    output.push_str("\tDevProductsById: { [number]: DevProductKey },\n");
+    output.push_str("\tGamepassesById: { [number]: GamepassKey },\n");
+    output.push_str("}\n\n");
 
-    dev_products.sort_by(|a, b| a.0.cmp(&b.0));
-    gamepasses.sort_by(|a, b| a.0.cmp(&b.0));
+    output
+}
+
+/// Keys by Roblox ID, for turning a receipt's product ID back into a key. If
+/// two keys share an ID, the first one wins.
+fn by_id<'a>(entries: &[Entry<'a>]) -> Vec<(u64, &'a str)> {
+    let mut seen = HashSet::new();
+    entries
+        .iter()
+        .filter(|entry| seen.insert(entry.id))
+        .map(|entry| (entry.id, entry.key))
+        .collect()
+}
+
+fn generate_dts(config: &Config, mapping: &Mapping, output_config: &OutputConfig) -> String {
+    let (dev_products, gamepasses) = entries(config, mapping, output_config);
+    let mode = output_config.mode;
 
     let mut output = String::new();
     output.push_str("// This file is auto-generated by spearmint. Do not edit manually.\n\n");
     output.push_str("interface Products {\n");
 
-    output.push_str("\tDevProducts: {\n");
-    for (key, id) in &dev_products {
-        output.push_str(&format!("\t\treadonly \"{}\": {};\n", key, id));
+    for (name, entries) in [("DevProducts", &dev_products), ("Gamepasses", &gamepasses)] {
+        output.push_str(&format!("\t{}: {{\n", name));
+        for entry in entries {
+            match mode {
+                OutputMode::Ids => {
+                    output.push_str(&format!(
+                        "\t\treadonly {}: {};\n",
+                        ts_string(entry.key),
+                        entry.id
+                    ));
+                }
+                OutputMode::Metadata => {
+                    let product = entry.product;
+                    let description = match product.description {
+                        Some(ref description) => ts_string(description),
+                        None => "undefined".to_string(),
+                    };
+                    output.push_str(&format!("\t\treadonly {}: {{\n", ts_string(entry.key)));
+                    output.push_str(&format!("\t\t\treadonly id: {};\n", entry.id));
+                    output.push_str(&format!(
+                        "\t\t\treadonly type: \"{}\";\n",
+                        product.product_type
+                    ));
+                    output.push_str(&format!(
+                        "\t\t\treadonly name: {};\n",
+                        ts_string(&product.name)
+                    ));
+                    output.push_str(&format!("\t\t\treadonly price: {};\n", product.price));
+                    output.push_str(&format!("\t\t\treadonly description: {};\n", description));
+                    output.push_str(&format!("\t\t\treadonly offsale: {};\n", product.offsale));
+                    output.push_str("\t\t};\n");
+                }
+            }
+        }
+        output.push_str("\t};\n");
     }
-    output.push_str("\t};\n");
 
-    output.push_str("\tGamepasses: {\n");
-    for (key, id) in &gamepasses {
-        output.push_str(&format!("\t\treadonly \"{}\": {};\n", key, id));
-    }
+    output.push_str("\tDevProductsById: {\n");
+    output.push_str("\t\treadonly [id: number]: keyof Products[\"DevProducts\"];\n");
+    output.push_str("\t};\n");
+    output.push_str("\tGamepassesById: {\n");
+    output.push_str("\t\treadonly [id: number]: keyof Products[\"Gamepasses\"];\n");
     output.push_str("\t};\n");
 
     output.push_str("}\n\n");
@@ -134,3 +267,159 @@ fn generate_dts(config: &Config, mapping: &Mapping) -> String {
 
     output
 }
+
+/// A product in JSON output's metadata mode, matching the Luau `ProductInfo`.
+#[derive(Serialize)]
+struct ProductRecord<'a> {
+    id: u64,
+    #[serde(rename = "type")]
+    product_type: String,
+    name: &'a str,
+    price: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    offsale: bool,
+}
+
+/// A JSON manifest laid out like the Luau module. Object keys must be
+/// strings, so the by-ID tables are keyed by the ID as a string.
+fn generate_json(config: &Config, mapping: &Mapping, output: &OutputConfig) -> Result<String> {
+    let (dev_products, gamepasses) = entries(config, mapping, output);
+
+    let mut manifest = IndexMap::new();
+    for (name, entries) in [("DevProducts", &dev_products), ("Gamepasses", &gamepasses)] {
+        let mut products = IndexMap::new();
+        for entry in entries {
+            let value = match output.mode {
+                OutputMode::Ids => serde_json::to_value(entry.id)?,
+                OutputMode::Metadata => serde_json::to_value(ProductRecord {
+                    id: entry.id,
+                    product_type: entry.product.product_type.to_string(),
+                    name: &entry.product.name,
+                    price: entry.product.price,
+                    description: entry.product.description.as_deref(),
+                    offsale: entry.product.offsale,
+                })?,
+            };
+            products.insert(entry.key.to_string(), value);
+        }
+        manifest.insert(name, products);
+    }
+    for (name, entries) in [
+        ("DevProductsById", &dev_products),
+        ("GamepassesById", &gamepasses),
+    ] {
+        let keys = by_id(entries)
+            .into_iter()
+            .map(|(id, key)| (id.to_string(), serde_json::Value::from(key)))
+            .collect();
+        manifest.insert(name, keys);
+    }
+
+    let mut json = serde_json::to_string_pretty(&manifest)?;
+    json.push('\n');
+    Ok(json)
+}
+
+/// A Luau string literal
+fn lua_string(value: &str) -> String {
+    let mut literal = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => literal.push_str("\\\""),
+            '\\' => literal.push_str("\\\\"),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c if c.is_control() => literal.push_str(&format!("\\u{{{:x}}}", c as u32)),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
+
+/// A TypeScript string literal, for keys and string literal types
+fn ts_string(value: &str) -> String {
+    serde_json::to_string(value).unwrap_or_else(|_| "string".to_string())
+}
+
+/// What a user template is rendered with.
+#[derive(Serialize)]
+struct TemplateContext<'a> {
+    /// Name of the output being rendered
+    output: &'a str,
+    universe_id: u64,
+    /// Every product with a Roblox ID, in config order
+    products: Vec<TemplateProduct<'a>>,
+    dev_products: Vec<TemplateProduct<'a>>,
+    gamepasses: Vec<TemplateProduct<'a>>,
+}
+
+#[derive(Serialize)]
+struct TemplateProduct<'a> {
+    key: &'a str,
+    id: u64,
+    #[serde(rename = "type")]
+    product_type: String,
+    name: &'a str,
+    price: u64,
+    price_tier: Option<&'a str>,
+    description: Option<&'a str>,
+    image: Option<&'a str>,
+    offsale: bool,
+    regional_pricing: Option<bool>,
+    store_page: Option<bool>,
+}
+
+impl<'a> From<&Entry<'a>> for TemplateProduct<'a> {
+    fn from(entry: &Entry<'a>) -> Self {
+        let product = entry.product;
+        Self {
+            key: entry.key,
+            id: entry.id,
+            product_type: product.product_type.to_string(),
+            name: &product.name,
+            price: product.price,
+            price_tier: product.price_tier.as_deref(),
+            description: product.description.as_deref(),
+            image: product.image.as_deref(),
+            offsale: product.offsale,
+            regional_pricing: product.regional_pricing,
+            store_page: product.store_page,
+        }
+    }
+}
+
+/// Render a user-supplied MiniJinja template. Undefined variables are errors,
+/// so a typo in the template fails instead of writing an empty value.
+fn render_template(
+    path: &str,
+    name: &str,
+    config: &Config,
+    mapping: &Mapping,
+    output: &OutputConfig,
+) -> Result<String> {
+    let source =
+        fs::read_to_string(path).with_context(|| format!("Failed to read template: {}", path))?;
+
+    let (dev_products, gamepasses) = entries(config, mapping, output);
+    let context = TemplateContext {
+        output: name,
+        universe_id: config.universe_id,
+        products: synced(config, mapping, output)
+            .iter()
+            .map(Into::into)
+            .collect(),
+        dev_products: dev_products.iter().map(Into::into).collect(),
+        gamepasses: gamepasses.iter().map(Into::into).collect(),
+    };
+
+    let mut env = Environment::new();
+    env.set_keep_trailing_newline(true);
+    env.set_undefined_behavior(UndefinedBehavior::Strict);
+    env.add_filter("lua_string", |value: &str| lua_string(value));
+
+    env.render_named_str(path, &source, &context)
+        .with_context(|| format!("Failed to render template: {}", path))
+}
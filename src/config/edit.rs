@@ -0,0 +1,164 @@
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use std::fs;
+use toml_edit::{value, Array, DocumentMut, Item, Table, TableLike};
+
+use super::{Config, Product, ProductType};
+
+/// Makes targeted changes to spearmint.toml and its included files, keeping
+/// comments, key order and formatting everywhere else.
+pub struct ConfigEditor {
+    config_path: String,
+    /// Files opened for editing, by path
+    documents: IndexMap<String, DocumentMut>,
+}
+
+impl ConfigEditor {
+    pub fn new(config_path: &str) -> Self {
+        Self {
+            config_path: config_path.to_string(),
+            documents: IndexMap::new(),
+        }
+    }
+
+    fn document(&mut self, path: &str) -> Result<&mut DocumentMut> {
+        if !self.documents.contains_key(path) {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read config file: {}", path))?;
+            let document: DocumentMut = content
+                .parse()
+                .with_context(|| format!("Failed to parse config file: {}", path))?;
+            self.documents.insert(path.to_string(), document);
+        }
+        Ok(&mut self.documents[path])
+    }
+
+    /// Add a new product to the end of the main config file.
+    pub fn add_product(&mut self, key: &str, product: &Product) -> Result<()> {
+        let config_path = self.config_path.clone();
+        let products = products_table(self.document(&config_path)?, &config_path)?;
+
+        if products.contains_key(key) {
+            anyhow::bail!("Product \"{}\" already exists in {}", key, config_path);
+        }
+        products.insert(key, Item::Table(product_table(product)));
+
+        Ok(())
+    }
+
+    /// Rename a product in the file that defines it, recording the old key in
+    /// `previous_keys`, and rename any environment overrides for it.
+    pub fn rename_product(&mut self, config: &Config, old: &str, new: &str) -> Result<()> {
+        let Some(product) = config.products.get(old) else {
+            anyhow::bail!("Product \"{}\" not found in {}", old, self.config_path);
+        };
+        let path = product
+            .source
+            .clone()
+            .unwrap_or_else(|| self.config_path.clone());
+
+        let products = products_table(self.document(&path)?, &path)?;
+        let fields = rename_key(products, old, new)
+            .and_then(Item::as_table_like_mut)
+            .with_context(|| format!("Product \"{}\" not found in {}", old, path))?;
+
+        match fields.get_mut("previous_keys").and_then(Item::as_array_mut) {
+            Some(previous_keys) => {
+                previous_keys.retain(|key| key.as_str() != Some(new));
+                if !previous_keys.iter().any(|key| key.as_str() == Some(old)) {
+                    previous_keys.push(old);
+                }
+            }
+            None => {
+                fields.insert("previous_keys", value(Array::from_iter([old])));
+            }
+        }
+
+        if !config.environments.is_empty() {
+            let config_path = self.config_path.clone();
+            let document = self.document(&config_path)?;
+            let environments = document
+                .get_mut("environments")
+                .and_then(Item::as_table_like_mut);
+
+            for (_, environment) in environments.into_iter().flat_map(|e| e.iter_mut()) {
+                let overrides = environment
+                    .as_table_like_mut()
+                    .and_then(|e| e.get_mut("overrides"))
+                    .and_then(Item::as_table_like_mut);
+                if let Some(overrides) = overrides {
+                    rename_key(overrides, old, new);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Write every file that was edited.
+    pub fn save(&self) -> Result<()> {
+        for (path, document) in &self.documents {
+            fs::write(path, document.to_string())
+                .with_context(|| format!("Failed to write config file: {}", path))?;
+        }
+        Ok(())
+    }
+}
+
+/// The `products` table of a document, created if the file has none yet.
+fn products_table<'a>(document: &'a mut DocumentMut, path: &str) -> Result<&'a mut dyn TableLike> {
+    let products = document.entry("products").or_insert_with(|| {
+        let mut table = Table::new();
+        table.set_implicit(true);
+        Item::Table(table)
+    });
+
+    products
+        .as_table_like_mut()
+        .with_context(|| format!("products in {} is not a table", path))
+}
+
+/// Move an entry to a new key. Tables keep their position in the file, so
+/// the entry stays where it was.
+fn rename_key<'a>(table: &'a mut dyn TableLike, old: &str, new: &str) -> Option<&'a mut Item> {
+    let item = table.remove(old)?;
+    table.insert(new, item);
+    table.get_mut(new)
+}
+
+fn product_table(product: &Product) -> Table {
+    let mut table = Table::new();
+
+    if let Some(ref extends) = product.extends {
+        table["extends"] = value(extends);
+    }
+    table["type"] = value(match product.product_type {
+        ProductType::DevProduct => "dev_product",
+        ProductType::Gamepass => "gamepass",
+    });
+    table["name"] = value(&product.name);
+    table["price"] = value(product.price as i64);
+    if let Some(ref description) = product.description {
+        table["description"] = value(description);
+    }
+    if let Some(ref image) = product.image {
+        table["image"] = value(image);
+    }
+    if let Some(product_id) = product.product_id {
+        table["product_id"] = value(product_id as i64);
+    }
+    if product.offsale {
+        table["offsale"] = value(true);
+    }
+    if let Some(regional_pricing) = product.regional_pricing {
+        table["regional_pricing"] = value(regional_pricing);
+    }
+    if let Some(store_page) = product.store_page {
+        table["store_page"] = value(store_page);
+    }
+    if !product.previous_keys.is_empty() {
+        table["previous_keys"] = value(Array::from_iter(&product.previous_keys));
+    }
+
+    table
+}
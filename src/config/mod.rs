@@ -0,0 +1,845 @@
+mod edit;
+mod price;
+
+pub use edit::ConfigEditor;
+pub use price::PriceTiers;
+
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+use schemars::JsonSchema;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use crate::api::{RetryPolicy, DEFAULT_API_URL};
+use crate::migrate;
+
+pub const DEFAULT_CONFIG_PATH: &str = "spearmint.toml";
+pub const DEFAULT_SCHEMA_PATH: &str = "spearmint.schema.json";
+/// Format version of spearmint.toml written by this build
+pub const CONFIG_VERSION: u32 = 2;
+/// Name given to the output of configs from before outputs were named
+pub const DEFAULT_OUTPUT: &str = "main";
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Config {
+    /// Config format version, upgraded by `spearmint migrate`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<u32>,
+    pub universe_id: u64,
+    /// Open Cloud base URL. Overridden by the SPEARMINT_API_URL environment variable.
+    pub api_url: Option<String>,
+    /// Generated files, by name
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub output: IndexMap<String, OutputConfig>,
+    pub orphans: Option<OrphanConfig>,
+    pub retry: Option<RetryPolicy>,
+    /// Glob patterns for extra product files, relative to this config
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Named prices that products can use in their `price`, e.g. "tier3 * 2"
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub price_tiers: PriceTiers,
+    /// Shared product fields, merged into products that `extends` them
+    #[serde(default, skip_serializing_if = "toml::Table::is_empty")]
+    #[schemars(with = "HashMap<String, TemplateSchema>")]
+    pub templates: toml::Table,
+    #[serde(default)]
+    pub products: IndexMap<String, Product>,
+    /// Named environments, selected with `--env`
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub environments: IndexMap<String, Environment>,
+}
+
+/// A named deployment target, such as a separate dev or production universe.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct Environment {
+    pub universe_id: u64,
+    /// Lock file path. Defaults to spearmint.<name>.lock.toml
+    pub lock: Option<String>,
+    /// Replaces the path of each named output, so each environment gets its
+    /// own modules
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub output: IndexMap<String, String>,
+    /// Per-product field overrides, keyed by product key
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub overrides: IndexMap<String, ProductOverride>,
+}
+
+/// Product fields that can differ between environments.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ProductOverride {
+    pub name: Option<String>,
+    #[schemars(with = "Option<PriceSchema>")]
+    pub price: Option<u64>,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub product_id: Option<u64>,
+    pub offsale: Option<bool>,
+    pub regional_pricing: Option<bool>,
+    pub store_page: Option<bool>,
+    /// The price tier expression `price` was computed from
+    #[serde(skip)]
+    #[schemars(skip)]
+    pub price_tier: Option<String>,
+}
+
+/// What sync does with lock entries whose key was removed from the config.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct OrphanConfig {
+    #[serde(default)]
+    pub policy: OrphanPolicy,
+    /// Prefix added to the name of archived products
+    #[serde(default = "default_archive_prefix")]
+    pub archive_prefix: String,
+}
+
+impl Default for OrphanConfig {
+    fn default() -> Self {
+        Self {
+            policy: OrphanPolicy::default(),
+            archive_prefix: default_archive_prefix(),
+        }
+    }
+}
+
+fn default_archive_prefix() -> String {
+    "[Archived] ".to_string()
+}
+
+#[derive(
+    Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, clap::ValueEnum, JsonSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum OrphanPolicy {
+    /// Leave the product on Roblox and in the lock file
+    #[default]
+    Keep,
+    /// Take the product off sale
+    Offsale,
+    /// Rename the product with the archive prefix and take it off sale
+    Archive,
+    /// Drop the product from the lock file, leaving it untouched on Roblox
+    Forget,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct OutputConfig {
+    pub path: String,
+    #[serde(default)]
+    pub format: OutputFormat,
+    /// Also write a .d.ts file next to a Luau module
+    #[serde(default)]
+    pub typescript: bool,
+    #[serde(default)]
+    pub mode: OutputMode,
+    /// Template file rendered in place of the built-in layout, relative to
+    /// this config
+    pub template: Option<String>,
+    /// Template file rendered in place of the built-in .d.ts layout, relative
+    /// to this config
+    pub typescript_template: Option<String>,
+    /// Which products to write. Defaults to all of them.
+    #[serde(default, skip_serializing_if = "ProductFilter::is_empty")]
+    pub filter: ProductFilter,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputFormat {
+    /// A Luau module
+    #[default]
+    Luau,
+    /// A JSON manifest with the same layout as the Luau module
+    Json,
+}
+
+/// Selects the products written to an output.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+pub struct ProductFilter {
+    /// Product types to include. Defaults to all types.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub types: Vec<ProductType>,
+    /// Glob patterns for keys to include, such as "coins_*". Defaults to all keys.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub include: Vec<String>,
+    /// Glob patterns for keys to leave out
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub exclude: Vec<String>,
+}
+
+impl ProductFilter {
+    pub fn is_empty(&self) -> bool {
+        self.types.is_empty() && self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    /// Whether a product passes the filter
+    pub fn matches(&self, key: &str, product_type: &ProductType) -> bool {
+        let any_match = |patterns: &[String]| {
+            patterns
+                .iter()
+                .any(|pattern| glob::Pattern::new(pattern).is_ok_and(|p| p.matches(key)))
+        };
+
+        (self.types.is_empty() || self.types.contains(product_type))
+            && (self.include.is_empty() || any_match(&self.include))
+            && !any_match(&self.exclude)
+    }
+}
+
+/// What the generated module holds for each product.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum OutputMode {
+    /// Just the Roblox ID
+    #[default]
+    Ids,
+    /// A record with the ID, type, name, price, description and offsale flag
+    Metadata,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+#[schemars(deny_unknown_fields)]
+#[schemars(transform = required_unless_extends)]
+pub struct Product {
+    #[serde(rename = "type")]
+    pub product_type: ProductType,
+    pub name: String,
+    #[schemars(with = "PriceSchema")]
+    pub price: u64,
+    pub description: Option<String>,
+    pub image: Option<String>,
+    pub product_id: Option<u64>,
+    /// If true, the gamepass will be off sale. Defaults to false (on sale).
+    /// Only applies to gamepasses, ignored for dev products.
+    #[serde(default)]
+    pub offsale: bool,
+    /// Let Roblox adjust the price for each region. Left as it is on Roblox
+    /// when unset.
+    pub regional_pricing: Option<bool>,
+    /// Show the product on the experience's store page. Left as it is on
+    /// Roblox when unset. Only applies to dev products.
+    pub store_page: Option<bool>,
+    /// Keys this product was previously known by. Lock entries under these
+    /// keys are moved to the current key instead of creating a new product.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub previous_keys: Vec<String>,
+    /// Template this product's fields were merged from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extends: Option<String>,
+    /// The included file this product is defined in, or None for the main config
+    #[serde(skip)]
+    pub source: Option<String>,
+    /// The price tier expression `price` was computed from, such as "tier3 * 2"
+    #[serde(skip)]
+    pub price_tier: Option<String>,
+}
+
+/// Schema for a price: Robux, or an expression over `[price_tiers]`.
+#[derive(JsonSchema)]
+#[serde(untagged)]
+#[allow(dead_code)]
+enum PriceSchema {
+    Robux(u64),
+    /// A tier name or expression, such as "tier3" or "tier3 * 2"
+    Tier(String),
+}
+
+/// Schema for a `[templates.*]` block, which may set any product field.
+#[derive(JsonSchema)]
+#[schemars(deny_unknown_fields)]
+#[allow(dead_code)]
+struct TemplateSchema {
+    #[serde(rename = "type")]
+    product_type: Option<ProductType>,
+    name: Option<String>,
+    price: Option<PriceSchema>,
+    /// May contain {name} and {price}, filled in from each product
+    description: Option<String>,
+    image: Option<String>,
+    offsale: Option<bool>,
+    regional_pricing: Option<bool>,
+    store_page: Option<bool>,
+    /// Template to inherit fields from
+    extends: Option<String>,
+}
+
+/// Products that `extends` a template may get their required fields from it.
+fn required_unless_extends(schema: &mut schemars::Schema) {
+    let Some(object) = schema.as_object_mut() else {
+        return;
+    };
+    if let Some(required) = object.remove("required") {
+        object.insert(
+            "if".into(),
+            serde_json::json!({ "not": { "required": ["extends"] } }),
+        );
+        object.insert("then".into(), serde_json::json!({ "required": required }));
+    }
+}
+
+/// The JSON Schema for spearmint.toml, for editor completion and validation.
+pub fn json_schema() -> Result<String> {
+    let schema = schemars::generate::SchemaSettings::draft07()
+        .into_generator()
+        .into_root_schema_for::<Config>();
+    Ok(serde_json::to_string_pretty(&schema)?)
+}
+
+/// A file listed in `include`, holding only products.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ProductFile {
+    #[serde(default)]
+    products: IndexMap<String, Product>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ProductType {
+    DevProduct,
+    Gamepass,
+}
+
+impl std::fmt::Display for ProductType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProductType::DevProduct => write!(f, "DevProduct"),
+            ProductType::Gamepass => write!(f, "Gamepass"),
+        }
+    }
+}
+
+impl Config {
+    /// The Open Cloud base URL to use, from the environment, config or default
+    pub fn api_url(&self) -> String {
+        std::env::var("SPEARMINT_API_URL")
+            .ok()
+            .filter(|url| !url.is_empty())
+            .or_else(|| self.api_url.clone())
+            .unwrap_or_else(|| DEFAULT_API_URL.to_string())
+    }
+
+    /// Resolve the config for a named environment, applying its universe,
+    /// output path and product overrides.
+    pub fn for_environment(&self, name: &str) -> Result<Config> {
+        let Some(env) = self.environments.get(name) else {
+            let names: Vec<&str> = self.environments.keys().map(String::as_str).collect();
+            if names.is_empty() {
+                anyhow::bail!(
+                    "Unknown environment \"{}\": no [environments] are defined",
+                    name
+                );
+            }
+            anyhow::bail!(
+                "Unknown environment \"{}\" (available: {})",
+                name,
+                names.join(", ")
+            );
+        };
+
+        let mut config = self.clone();
+        config.universe_id = env.universe_id;
+
+        for (output, path) in &env.output {
+            config.output[output].path = path.clone();
+        }
+
+        for (key, fields) in &env.overrides {
+            if let Some(product) = config.products.get_mut(key) {
+                fields.apply(product);
+            }
+        }
+
+        validate_no_duplicate_names(&config)
+            .with_context(|| format!("Invalid overrides in environment \"{}\"", name))?;
+
+        Ok(config)
+    }
+}
+
+impl Environment {
+    /// The lock file for this environment, from the config or the default
+    pub fn lock_path(&self, name: &str) -> String {
+        self.lock
+            .clone()
+            .unwrap_or_else(|| format!("spearmint.{}.lock.toml", name))
+    }
+}
+
+impl ProductOverride {
+    fn apply(&self, product: &mut Product) {
+        if let Some(ref name) = self.name {
+            product.name = name.clone();
+        }
+        if let Some(price) = self.price {
+            product.price = price;
+            product.price_tier = self.price_tier.clone();
+        }
+        if let Some(ref description) = self.description {
+            product.description = Some(description.clone());
+        }
+        if let Some(ref image) = self.image {
+            product.image = Some(image.clone());
+        }
+        if let Some(product_id) = self.product_id {
+            product.product_id = Some(product_id);
+        }
+        if let Some(offsale) = self.offsale {
+            product.offsale = offsale;
+        }
+        if let Some(regional_pricing) = self.regional_pricing {
+            product.regional_pricing = Some(regional_pricing);
+        }
+        if let Some(store_page) = self.store_page {
+            product.store_page = Some(store_page);
+        }
+    }
+}
+
+pub fn load(config_path: &str) -> Result<Config> {
+    let path = Path::new(config_path);
+
+    if !path.exists() {
+        anyhow::bail!("Config file not found: {}", path.display());
+    }
+
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    // Older configs are upgraded as they are read. `spearmint migrate` writes
+    // the upgrade back to the file.
+    let mut table = migrate::upgrade_config(&content, config_path)?;
+
+    let templates = match table.get("templates") {
+        Some(toml::Value::Table(templates)) => templates.clone(),
+        _ => toml::Table::new(),
+    };
+    let tiers: PriceTiers = match table.get("price_tiers") {
+        Some(tiers) => tiers
+            .clone()
+            .try_into()
+            .with_context(|| format!("Invalid price_tiers in {}", path.display()))?,
+        None => PriceTiers::new(),
+    };
+
+    let mut product_tiers = IndexMap::new();
+    if let Some(toml::Value::Table(products)) = table.get_mut("products") {
+        product_tiers = resolve_products(products, &templates, &tiers)?;
+    }
+    let override_tiers = resolve_overrides(&mut table, &tiers)?;
+
+    let mut config: Config = deserialize_table(table, path)?;
+    for (key, tier) in product_tiers {
+        config.products[&key].price_tier = Some(tier);
+    }
+    for ((env, key), tier) in override_tiers {
+        config.environments[&env].overrides[&key].price_tier = Some(tier);
+    }
+    load_includes(&mut config, path)?;
+    resolve_template_paths(&mut config, path);
+
+    validate_no_duplicate_names(&config)?;
+    validate_previous_keys(&config)?;
+    validate_environments(&config)?;
+    validate_outputs(&config)?;
+
+    Ok(config)
+}
+
+fn read_table(path: &Path) -> Result<toml::Table> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+
+    toml::from_str(&content)
+        .with_context(|| format!("Failed to parse config file: {}", path.display()))
+}
+
+fn deserialize_table<T: DeserializeOwned>(table: toml::Table, path: &Path) -> Result<T> {
+    toml::Value::Table(table)
+        .try_into()
+        .with_context(|| format!("Failed to parse config file: {}", path.display()))
+}
+
+/// Merge template fields into every product that `extends` one and compute
+/// tier prices. Fields set on the product win, and `{name}` and `{price}` in an
+/// inherited description are filled in from the product.
+///
+/// Returns the price expression of every product priced by tier.
+fn resolve_products(
+    products: &mut toml::Table,
+    templates: &toml::Table,
+    tiers: &PriceTiers,
+) -> Result<IndexMap<String, String>> {
+    let mut product_tiers = IndexMap::new();
+
+    for (key, product) in products.iter_mut() {
+        let Some(product) = product.as_table_mut() else {
+            continue;
+        };
+        let tier = inherit(product, templates, tiers)
+            .with_context(|| format!("Invalid product \"{}\"", key))?;
+        if let Some(tier) = tier {
+            product_tiers.insert(key.clone(), tier);
+        }
+    }
+
+    Ok(product_tiers)
+}
+
+fn inherit(
+    product: &mut toml::Table,
+    templates: &toml::Table,
+    tiers: &PriceTiers,
+) -> Result<Option<String>> {
+    let Some(extends) = product.get("extends") else {
+        return resolve_price(product, tiers);
+    };
+    let name = extends
+        .as_str()
+        .context("extends must be a template name")?;
+    let fields = resolve_template(templates, name, &mut Vec::new())?;
+
+    let own_description = product.contains_key("description");
+    for (field, value) in fields {
+        product.entry(field).or_insert(value);
+    }
+
+    // The price may come from the template, and is needed for {price}
+    let tier = resolve_price(product, tiers)?;
+
+    if !own_description {
+        let name = product
+            .get("name")
+            .and_then(|v| v.as_str())
+            .map(str::to_string);
+        let price = product.get("price").and_then(|v| v.as_integer());
+        if let Some(toml::Value::String(description)) = product.get_mut("description") {
+            if let Some(name) = name {
+                *description = description.replace("{name}", &name);
+            }
+            if let Some(price) = price {
+                *description = description.replace("{price}", &price.to_string());
+            }
+        }
+    }
+
+    Ok(tier)
+}
+
+/// Replace a tier expression in `price` with the Robux amount it comes to,
+/// returning the expression.
+fn resolve_price(fields: &mut toml::Table, tiers: &PriceTiers) -> Result<Option<String>> {
+    let Some(toml::Value::String(expression)) = fields.get("price") else {
+        return Ok(None);
+    };
+    let expression = expression.clone();
+
+    let price = price::evaluate(&expression, tiers)?;
+    let price = i64::try_from(price).context("Price is too large")?;
+    fields.insert("price".to_string(), toml::Value::Integer(price));
+
+    Ok(Some(expression))
+}
+
+/// Compute tier prices in environment overrides. Returns the price expression
+/// of every override priced by tier, keyed by environment and product.
+fn resolve_overrides(
+    table: &mut toml::Table,
+    tiers: &PriceTiers,
+) -> Result<IndexMap<(String, String), String>> {
+    let mut override_tiers = IndexMap::new();
+
+    let Some(toml::Value::Table(environments)) = table.get_mut("environments") else {
+        return Ok(override_tiers);
+    };
+    for (env, environment) in environments.iter_mut() {
+        let Some(toml::Value::Table(overrides)) = environment.get_mut("overrides") else {
+            continue;
+        };
+        for (key, fields) in overrides.iter_mut() {
+            let Some(fields) = fields.as_table_mut() else {
+                continue;
+            };
+            let tier = resolve_price(fields, tiers).with_context(|| {
+                format!(
+                    "Invalid override for \"{}\" in environment \"{}\"",
+                    key, env
+                )
+            })?;
+            if let Some(tier) = tier {
+                override_tiers.insert((env.clone(), key.clone()), tier);
+            }
+        }
+    }
+
+    Ok(override_tiers)
+}
+
+/// The fields of a template, including those it inherits through `extends`.
+fn resolve_template(
+    templates: &toml::Table,
+    name: &str,
+    chain: &mut Vec<String>,
+) -> Result<toml::Table> {
+    if chain.iter().any(|n| n == name) {
+        anyhow::bail!(
+            "Template \"{}\" extends itself ({} -> {})",
+            name,
+            chain.join(" -> "),
+            name
+        );
+    }
+    let Some(template) = templates.get(name).and_then(|t| t.as_table()) else {
+        anyhow::bail!("Unknown template \"{}\"", name);
+    };
+    chain.push(name.to_string());
+
+    let mut fields = template.clone();
+    if let Some(parent) = fields.remove("extends") {
+        let parent = parent.as_str().context("extends must be a template name")?;
+        for (field, value) in resolve_template(templates, parent, chain)? {
+            fields.entry(field).or_insert(value);
+        }
+    }
+
+    Ok(fields)
+}
+
+/// Make output template paths relative to the config file, like `include`.
+fn resolve_template_paths(config: &mut Config, config_path: &Path) {
+    let base = config_path.parent().unwrap_or(Path::new(""));
+
+    for output in config.output.values_mut() {
+        let templates = [&mut output.template, &mut output.typescript_template];
+        for template in templates.into_iter().flatten() {
+            *template = base.join(&*template).to_string_lossy().into_owned();
+        }
+    }
+}
+
+/// Merge the products from every file matched by `include` into the config.
+fn load_includes(config: &mut Config, config_path: &Path) -> Result<()> {
+    let base = config_path.parent().unwrap_or(Path::new(""));
+    let config_name = config_path.display().to_string();
+
+    for pattern in &config.include {
+        let full_pattern = base.join(pattern);
+        let full_pattern = full_pattern.to_string_lossy();
+        let mut paths = glob::glob(&full_pattern)
+            .with_context(|| format!("Invalid include pattern: {}", pattern))?
+            .collect::<Result<Vec<_>, _>>()?;
+        paths.sort();
+
+        if paths.is_empty() && !pattern.contains(['*', '?', '[']) {
+            anyhow::bail!("Included file not found: {}", full_pattern);
+        }
+
+        for path in paths {
+            let mut table = read_table(&path)?;
+            let mut product_tiers = IndexMap::new();
+            if let Some(toml::Value::Table(products)) = table.get_mut("products") {
+                product_tiers = resolve_products(products, &config.templates, &config.price_tiers)?;
+            }
+            let file: ProductFile = deserialize_table(table, &path)?;
+            let source = path.display().to_string();
+
+            for (key, mut product) in file.products {
+                if let Some(existing) = config.products.get(&key) {
+                    let existing = existing.source.as_deref().unwrap_or(&config_name);
+                    anyhow::bail!(
+                        "Duplicate product key \"{}\" in {} and {}",
+                        key,
+                        existing,
+                        source
+                    );
+                }
+
+                product.source = Some(source.clone());
+                product.price_tier = product_tiers.get(&key).cloned();
+                config.products.insert(key, product);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_no_duplicate_names(config: &Config) -> Result<()> {
+    let mut dev_product_names: HashMap<&str, &str> = HashMap::new();
+    let mut gamepass_names: HashMap<&str, &str> = HashMap::new();
+
+    for (key, product) in &config.products {
+        let name_map = match product.product_type {
+            ProductType::DevProduct => &mut dev_product_names,
+            ProductType::Gamepass => &mut gamepass_names,
+        };
+
+        if let Some(existing) = name_map.get(product.name.as_str()) {
+            anyhow::bail!(
+                "Duplicate {} name \"{}\" found in keys \"{}\" and \"{}\"",
+                product.product_type,
+                product.name,
+                existing,
+                key
+            );
+        }
+
+        name_map.insert(&product.name, key);
+    }
+
+    Ok(())
+}
+
+fn validate_previous_keys(config: &Config) -> Result<()> {
+    let mut claimed: HashMap<&str, &str> = HashMap::new();
+
+    for (key, product) in &config.products {
+        for previous in &product.previous_keys {
+            if config.products.contains_key(previous) {
+                anyhow::bail!(
+                    "Product \"{}\" lists \"{}\" in previous_keys, but that key is still in use",
+                    key,
+                    previous
+                );
+            }
+
+            if let Some(existing) = claimed.insert(previous, key) {
+                anyhow::bail!(
+                    "Previous key \"{}\" is claimed by both \"{}\" and \"{}\"",
+                    previous,
+                    existing,
+                    key
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_environments(config: &Config) -> Result<()> {
+    for (name, env) in &config.environments {
+        for key in env.overrides.keys() {
+            if !config.products.contains_key(key) {
+                anyhow::bail!(
+                    "Environment \"{}\" overrides unknown product \"{}\"",
+                    name,
+                    key
+                );
+            }
+        }
+        for output in env.output.keys() {
+            if !config.output.contains_key(output) {
+                let names: Vec<&str> = config.output.keys().map(String::as_str).collect();
+                if names.is_empty() {
+                    anyhow::bail!(
+                        "Environment \"{}\" sets the path of unknown output \"{}\": no [output] is defined",
+                        name,
+                        output
+                    );
+                }
+                anyhow::bail!(
+                    "Environment \"{}\" sets the path of unknown output \"{}\" (available: {})",
+                    name,
+                    output,
+                    names.join(", ")
+                );
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn validate_outputs(config: &Config) -> Result<()> {
+    for (name, output) in &config.output {
+        if output.typescript && output.format == OutputFormat::Json {
+            anyhow::bail!(
+                "Output \"{}\" sets typescript, which only applies to Luau outputs",
+                name
+            );
+        }
+        let filter = &output.filter;
+        for pattern in filter.include.iter().chain(&filter.exclude) {
+            glob::Pattern::new(pattern).with_context(|| {
+                format!(
+                    "Invalid filter pattern \"{}\" in output \"{}\"",
+                    pattern, name
+                )
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+pub fn create_default() -> Config {
+    let mut products = IndexMap::new();
+
+    products.insert(
+        "example_product".to_string(),
+        Product {
+            product_type: ProductType::DevProduct,
+            name: "Example Product".to_string(),
+            price: 100,
+            description: Some("An example developer product".to_string()),
+            image: None,
+            product_id: None,
+            offsale: false,
+            regional_pricing: None,
+            store_page: None,
+            previous_keys: Vec::new(),
+            extends: None,
+            source: None,
+            price_tier: None,
+        },
+    );
+
+    products.insert(
+        "example_gamepass".to_string(),
+        Product {
+            product_type: ProductType::Gamepass,
+            name: "Example Gamepass".to_string(),
+            price: 500,
+            description: Some("An example gamepass".to_string()),
+            image: None,
+            product_id: None,
+            offsale: false,
+            regional_pricing: None,
+            store_page: None,
+            previous_keys: Vec::new(),
+            extends: None,
+            source: None,
+            price_tier: None,
+        },
+    );
+
+    Config {
+        version: Some(CONFIG_VERSION),
+        universe_id: 123456789,
+        api_url: None,
+        output: IndexMap::from([(
+            DEFAULT_OUTPUT.to_string(),
+            OutputConfig {
+                path: "src/shared/modules/Products.luau".to_string(),
+                typescript: true,
+                ..OutputConfig::default()
+            },
+        )]),
+        orphans: None,
+        retry: None,
+        include: Vec::new(),
+        price_tiers: PriceTiers::new(),
+        templates: toml::Table::new(),
+        products,
+        environments: IndexMap::new(),
+    }
+}
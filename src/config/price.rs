@@ -0,0 +1,236 @@
+use anyhow::{Context, Result};
+use indexmap::IndexMap;
+
+/// Named prices from `[price_tiers]`, in Robux.
+pub type PriceTiers = IndexMap<String, u64>;
+
+/// Evaluate a price expression such as `tier3` or `tier3 * 2`.
+///
+/// Expressions are made of tier names, whole numbers, `+`, `-`, `*`, `/` and
+/// parentheses. Division rounds down.
+pub fn evaluate(expression: &str, tiers: &PriceTiers) -> Result<u64> {
+    evaluate_signed(expression, tiers)
+        .and_then(|price| u64::try_from(price).context("Price is negative"))
+        .with_context(|| format!("Invalid price \"{}\"", expression))
+}
+
+fn evaluate_signed(expression: &str, tiers: &PriceTiers) -> Result<i64> {
+    let mut parser = Parser {
+        tokens: tokenize(expression)?,
+        position: 0,
+        tiers,
+    };
+
+    let price = parser.sum()?;
+    if let Some(token) = parser.tokens.get(parser.position) {
+        anyhow::bail!("Unexpected {}", token);
+    }
+
+    Ok(price)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(i64),
+    Tier(String),
+    Operator(char),
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "\"{}\"", n),
+            Token::Tier(name) => write!(f, "\"{}\"", name),
+            Token::Operator(op) => write!(f, "\"{}\"", op),
+        }
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c.is_ascii_digit() {
+            let mut digits = String::new();
+            while let Some(&d) = chars.peek().filter(|d| d.is_ascii_digit()) {
+                digits.push(d);
+                chars.next();
+            }
+            let number = digits
+                .parse()
+                .with_context(|| format!("Number {} is too large", digits))?;
+            tokens.push(Token::Number(number));
+        } else if c.is_ascii_alphabetic() || c == '_' {
+            let mut name = String::new();
+            while let Some(&d) = chars
+                .peek()
+                .filter(|d| d.is_ascii_alphanumeric() || **d == '_')
+            {
+                name.push(d);
+                chars.next();
+            }
+            tokens.push(Token::Tier(name));
+        } else if "+-*/()".contains(c) {
+            tokens.push(Token::Operator(c));
+            chars.next();
+        } else {
+            anyhow::bail!("Unexpected \"{}\"", c);
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token>,
+    position: usize,
+    tiers: &'a PriceTiers,
+}
+
+impl Parser<'_> {
+    fn next_operator(&mut self, operators: &str) -> Option<char> {
+        match self.tokens.get(self.position) {
+            Some(Token::Operator(op)) if operators.contains(*op) => {
+                self.position += 1;
+                Some(*op)
+            }
+            _ => None,
+        }
+    }
+
+    /// product (("+" | "-") product)*
+    fn sum(&mut self) -> Result<i64> {
+        let mut value = self.product()?;
+        while let Some(op) = self.next_operator("+-") {
+            let rhs = self.product()?;
+            value = match op {
+                '+' => value.checked_add(rhs),
+                _ => value.checked_sub(rhs),
+            }
+            .context("Price is too large")?;
+        }
+        Ok(value)
+    }
+
+    /// factor (("*" | "/") factor)*
+    fn product(&mut self) -> Result<i64> {
+        let mut value = self.factor()?;
+        while let Some(op) = self.next_operator("*/") {
+            let rhs = self.factor()?;
+            value = match op {
+                '*' => value.checked_mul(rhs).context("Price is too large")?,
+                _ if rhs == 0 => anyhow::bail!("Price divides by zero"),
+                _ => value.div_euclid(rhs),
+            };
+        }
+        Ok(value)
+    }
+
+    /// number | tier | "(" sum ")"
+    fn factor(&mut self) -> Result<i64> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+
+        match token {
+            Some(Token::Number(n)) => Ok(n),
+            Some(Token::Tier(name)) => {
+                let Some(&price) = self.tiers.get(&name) else {
+                    let names: Vec<&str> = self.tiers.keys().map(String::as_str).collect();
+                    if names.is_empty() {
+                        anyhow::bail!(
+                            "Unknown price tier \"{}\": no [price_tiers] are defined",
+                            name
+                        );
+                    }
+                    anyhow::bail!(
+                        "Unknown price tier \"{}\" (available: {})",
+                        name,
+                        names.join(", ")
+                    );
+                };
+                i64::try_from(price).context("Price is too large")
+            }
+            Some(Token::Operator('(')) => {
+                let value = self.sum()?;
+                if self.next_operator(")").is_none() {
+                    anyhow::bail!("Missing \")\"");
+                }
+                Ok(value)
+            }
+            Some(token) => anyhow::bail!("Unexpected {}", token),
+            None => anyhow::bail!("Expression ends early"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiers() -> PriceTiers {
+        PriceTiers::from([
+            ("tier1".to_string(), 49),
+            ("tier2".to_string(), 99),
+            ("tier3".to_string(), 199),
+        ])
+    }
+
+    fn error(expression: &str) -> String {
+        format!("{:#}", evaluate(expression, &tiers()).unwrap_err())
+    }
+
+    #[test]
+    fn evaluates_tiers_and_numbers() {
+        assert_eq!(evaluate("tier2", &tiers()).unwrap(), 99);
+        assert_eq!(evaluate(" 150 ", &tiers()).unwrap(), 150);
+        assert_eq!(evaluate("tier3 * 2 + 1", &tiers()).unwrap(), 399);
+    }
+
+    #[test]
+    fn multiplication_binds_tighter_than_addition() {
+        assert_eq!(evaluate("1 + tier1 * 2", &tiers()).unwrap(), 99);
+        assert_eq!(evaluate("tier2 - 10 - 9", &tiers()).unwrap(), 80);
+        assert_eq!(evaluate("(1 + tier1) * 2", &tiers()).unwrap(), 100);
+        assert_eq!(evaluate("((tier1 + 1)) * (2)", &tiers()).unwrap(), 100);
+    }
+
+    #[test]
+    fn division_rounds_down() {
+        assert_eq!(evaluate("tier2 / 2", &tiers()).unwrap(), 49);
+        assert_eq!(evaluate("tier3 / 10 * 10", &tiers()).unwrap(), 190);
+        assert!(error("tier1 / 0").contains("Price divides by zero"));
+    }
+
+    #[test]
+    fn rejects_negative_and_overflowing_prices() {
+        assert!(error("tier1 - tier2").contains("Price is negative"));
+        assert_eq!(evaluate("tier1 - tier2 + 100", &tiers()).unwrap(), 50);
+        assert!(error("99999999999 * 99999999999").contains("Price is too large"));
+        assert!(error("99999999999999999999").contains("is too large"));
+    }
+
+    #[test]
+    fn reports_unknown_tiers() {
+        assert_eq!(
+            error("tier4 * 2"),
+            "Invalid price \"tier4 * 2\": Unknown price tier \"tier4\" (available: tier1, tier2, tier3)"
+        );
+        assert!(
+            format!("{:#}", evaluate("tier1", &PriceTiers::new()).unwrap_err())
+                .contains("no [price_tiers] are defined")
+        );
+    }
+
+    #[test]
+    fn rejects_malformed_expressions() {
+        assert!(error("tier1 tier2").contains("Unexpected \"tier2\""));
+        assert!(error("tier1 +").contains("Expression ends early"));
+        assert!(error("(tier1 + 1").contains("Missing \")\""));
+        assert!(error("tier1 )").contains("Unexpected \")\""));
+        assert!(error("tier1 % 2").contains("Unexpected \"%\""));
+        assert!(error("").contains("Expression ends early"));
+    }
+}
@@ -0,0 +1,263 @@
+use anyhow::Result;
+
+use crate::api::{Client, DevProductDetails, GamepassDetails};
+use crate::config::{Config, Product, ProductType};
+use crate::sync::{Mapping, MappingEntry};
+
+/// Product state as currently live on Roblox.
+#[derive(Debug, Clone)]
+pub struct RemoteProduct {
+    pub name: String,
+    pub price: Option<u64>,
+    pub description: Option<String>,
+    pub offsale: bool,
+    pub regional_pricing: bool,
+    /// Only reported for dev products
+    pub store_page: Option<bool>,
+}
+
+impl From<DevProductDetails> for RemoteProduct {
+    fn from(details: DevProductDetails) -> Self {
+        Self {
+            name: details.name,
+            price: details
+                .price_information
+                .as_ref()
+                .and_then(|p| p.default_price_in_robux),
+            description: details.description.filter(|d| !d.is_empty()),
+            offsale: !details.is_for_sale,
+            regional_pricing: details
+                .price_information
+                .is_some_and(|p| p.regional_pricing()),
+            store_page: details.store_page_enabled,
+        }
+    }
+}
+
+impl From<GamepassDetails> for RemoteProduct {
+    fn from(details: GamepassDetails) -> Self {
+        Self {
+            name: details.name,
+            price: details
+                .price_information
+                .as_ref()
+                .and_then(|p| p.default_price_in_robux),
+            description: details.description.filter(|d| !d.is_empty()),
+            offsale: !details.is_for_sale,
+            regional_pricing: details
+                .price_information
+                .is_some_and(|p| p.regional_pricing()),
+            store_page: None,
+        }
+    }
+}
+
+/// A field whose remote value disagrees with the config or the lock file.
+#[derive(Debug)]
+pub struct FieldDrift {
+    pub field: &'static str,
+    pub config: Option<String>,
+    pub lock: Option<String>,
+    pub remote: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ProductDrift<'a> {
+    pub key: &'a str,
+    pub product: &'a Product,
+    pub roblox_id: u64,
+    pub remote: RemoteProduct,
+    pub fields: Vec<FieldDrift>,
+}
+
+#[derive(Debug, Default)]
+pub struct DriftReport<'a> {
+    pub drifted: Vec<ProductDrift<'a>>,
+    pub in_sync: usize,
+    pub errors: Vec<(&'a str, anyhow::Error)>,
+}
+
+impl DriftReport<'_> {
+    pub fn has_drift(&self) -> bool {
+        !self.drifted.is_empty()
+    }
+
+    pub fn print(&self) {
+        for drift in &self.drifted {
+            println!(
+                "  ~ {} - {} (ID: {})",
+                drift.product.product_type, drift.key, drift.roblox_id
+            );
+            for field in &drift.fields {
+                println!(
+                    "      {}: config {}, lock {}, remote {}",
+                    field.field,
+                    field.config.as_deref().unwrap_or("(none)"),
+                    field.lock.as_deref().unwrap_or("(none)"),
+                    field.remote.as_deref().unwrap_or("(none)")
+                );
+            }
+        }
+
+        for (key, error) in &self.errors {
+            println!("  [ERROR] {}: {}", key, error);
+        }
+
+        println!(
+            "\nDrift: {} drifted, {} in sync, {} failed",
+            self.drifted.len(),
+            self.in_sync,
+            self.errors.len()
+        );
+    }
+}
+
+/// Fetch every synced product from Roblox and compare it to the config and lock file.
+pub async fn detect<'a>(client: &Client, config: &'a Config, mapping: &Mapping) -> DriftReport<'a> {
+    let mut report = DriftReport::default();
+
+    for (key, product) in &config.products {
+        let entry = mapping.get(key);
+        let Some(roblox_id) = product.product_id.or_else(|| entry.map(|e| e.roblox_id)) else {
+            continue;
+        };
+
+        let remote = match fetch_remote(client, config.universe_id, product, roblox_id).await {
+            Ok(remote) => remote,
+            Err(e) => {
+                report.errors.push((key, e));
+                continue;
+            }
+        };
+
+        let fields = compare(product, entry, &remote);
+        if fields.is_empty() {
+            report.in_sync += 1;
+        } else {
+            report.drifted.push(ProductDrift {
+                key,
+                product,
+                roblox_id,
+                remote,
+                fields,
+            });
+        }
+    }
+
+    report
+}
+
+/// Overwrite lock entries with the live Roblox state, so the next plan
+/// pushes the config back over any changes made outside spearmint.
+pub fn refresh_mapping(report: &DriftReport<'_>, mapping: &mut Mapping) {
+    for drift in &report.drifted {
+        let Some(entry) = mapping.get_mut(drift.key) else {
+            continue;
+        };
+
+        entry.name = Some(drift.remote.name.clone());
+        // Off-sale gamepasses have no price, so keep the locked one
+        if drift.remote.price.is_some() {
+            entry.price = drift.remote.price;
+        }
+        entry.description = drift.remote.description.clone();
+        if drift.product.product_type == ProductType::Gamepass {
+            entry.offsale = Some(drift.remote.offsale);
+        }
+        if drift.product.regional_pricing.is_some() {
+            entry.regional_pricing = Some(drift.remote.regional_pricing);
+        }
+        if drift.product.store_page.is_some() {
+            entry.store_page = drift.remote.store_page;
+        }
+    }
+}
+
+async fn fetch_remote(
+    client: &Client,
+    universe_id: u64,
+    product: &Product,
+    roblox_id: u64,
+) -> Result<RemoteProduct> {
+    let remote = match product.product_type {
+        ProductType::DevProduct => client.get_dev_product(universe_id, roblox_id).await?.into(),
+        ProductType::Gamepass => client.get_gamepass(universe_id, roblox_id).await?.into(),
+    };
+
+    Ok(remote)
+}
+
+fn compare(
+    product: &Product,
+    entry: Option<&MappingEntry>,
+    remote: &RemoteProduct,
+) -> Vec<FieldDrift> {
+    let mut fields = Vec::new();
+
+    let mut push = |field, config: Option<String>, lock: Option<String>, remote: Option<String>| {
+        // Without a lock entry there is nothing to compare against but the config
+        let lock_differs = entry.is_some() && lock != remote;
+        if config != remote || lock_differs {
+            fields.push(FieldDrift {
+                field,
+                config,
+                lock,
+                remote,
+            });
+        }
+    };
+
+    push(
+        "name",
+        Some(format!("{:?}", product.name)),
+        entry
+            .and_then(|e| e.name.as_ref())
+            .map(|n| format!("{:?}", n)),
+        Some(format!("{:?}", remote.name)),
+    );
+    // Off-sale gamepasses have no price to compare
+    if let Some(remote_price) = remote.price {
+        push(
+            "price",
+            Some(product.price.to_string()),
+            entry.and_then(|e| e.price).map(|p| p.to_string()),
+            Some(remote_price.to_string()),
+        );
+    }
+    push(
+        "description",
+        product.description.as_ref().map(|d| format!("{:?}", d)),
+        entry
+            .and_then(|e| e.description.as_ref())
+            .map(|d| format!("{:?}", d)),
+        remote.description.as_ref().map(|d| format!("{:?}", d)),
+    );
+    if product.product_type == ProductType::Gamepass {
+        push(
+            "offsale",
+            Some(product.offsale.to_string()),
+            entry.and_then(|e| e.offsale).map(|o| o.to_string()),
+            Some(remote.offsale.to_string()),
+        );
+    }
+    if let Some(regional_pricing) = product.regional_pricing {
+        push(
+            "regional_pricing",
+            Some(regional_pricing.to_string()),
+            entry
+                .and_then(|e| e.regional_pricing)
+                .map(|r| r.to_string()),
+            Some(remote.regional_pricing.to_string()),
+        );
+    }
+    if let (Some(store_page), Some(remote_store_page)) = (product.store_page, remote.store_page) {
+        push(
+            "store_page",
+            Some(store_page.to_string()),
+            entry.and_then(|e| e.store_page).map(|s| s.to_string()),
+            Some(remote_store_page.to_string()),
+        );
+    }
+
+    fields
+}
@@ -0,0 +1,158 @@
+use anyhow::Result;
+use std::collections::HashSet;
+
+use crate::api::Client;
+use crate::config::{Config, Product, ProductType};
+use crate::drift::RemoteProduct;
+use crate::sync::{Mapping, MappingEntry};
+use crate::validate::MIN_PRICE;
+
+/// A product that exists on Roblox but is not yet tracked by spearmint.
+#[derive(Debug)]
+pub struct ImportedProduct {
+    pub key: String,
+    pub product: Product,
+    pub entry: MappingEntry,
+}
+
+/// Fetch every dev product and gamepass in the universe that is not already
+/// in the config or lock file.
+pub async fn fetch_untracked(
+    client: &Client,
+    config: &Config,
+    mapping: &Mapping,
+) -> Result<Vec<ImportedProduct>> {
+    let tracked: HashSet<(ProductType, u64)> = config
+        .products
+        .iter()
+        .filter_map(|(key, product)| {
+            let id = product
+                .product_id
+                .or_else(|| mapping.get(key).map(|m| m.roblox_id))?;
+            Some((product.product_type.clone(), id))
+        })
+        .collect();
+
+    let mut remote: Vec<(ProductType, u64, RemoteProduct)> = Vec::new();
+
+    for details in client.list_dev_products(config.universe_id).await? {
+        remote.push((ProductType::DevProduct, details.product_id, details.into()));
+    }
+    for details in client.list_gamepasses(config.universe_id).await? {
+        remote.push((ProductType::Gamepass, details.game_pass_id, details.into()));
+    }
+
+    let mut used_keys: HashSet<String> = config.products.keys().cloned().collect();
+    used_keys.extend(mapping.keys().cloned());
+
+    let mut imported = Vec::new();
+
+    for (product_type, roblox_id, remote) in remote {
+        if tracked.contains(&(product_type.clone(), roblox_id)) {
+            continue;
+        }
+
+        // Off-sale gamepasses have no price. They are imported off sale with
+        // the lowest valid price, so the config passes validation and the
+        // next sync leaves them alone. Dev products can't be taken off sale.
+        let price = match remote.price {
+            Some(price) => price,
+            None if product_type == ProductType::Gamepass => {
+                eprintln!(
+                    "  Warning: {} \"{}\" has no price, importing it off sale with price {}",
+                    product_type, remote.name, MIN_PRICE
+                );
+                MIN_PRICE
+            }
+            None => {
+                eprintln!(
+                    "  Warning: {} \"{}\" (ID: {}) has no price, skipping it",
+                    product_type, remote.name, roblox_id
+                );
+                continue;
+            }
+        };
+
+        let key = unique_key(&remote.name, &product_type, roblox_id, &mut used_keys);
+        // Offsale is only tracked for gamepasses
+        let offsale =
+            product_type == ProductType::Gamepass && (remote.offsale || remote.price.is_none());
+        // Flags are only written to the config when they are turned on
+        let regional_pricing = remote.regional_pricing.then_some(true);
+        let store_page = remote.store_page.filter(|enabled| *enabled);
+
+        let entry = MappingEntry {
+            roblox_id,
+            name: Some(remote.name.clone()),
+            price: Some(price),
+            description: remote.description.clone(),
+            image_hash: None,
+            offsale: (product_type == ProductType::Gamepass).then_some(offsale),
+            product_type: Some(product_type.clone()),
+            regional_pricing,
+            store_page,
+        };
+
+        let product = Product {
+            product_type,
+            name: remote.name,
+            price,
+            description: remote.description,
+            image: None,
+            // IDs differ between environments, so they live in the lock file
+            product_id: None,
+            offsale,
+            regional_pricing,
+            store_page,
+            previous_keys: Vec::new(),
+            extends: None,
+            source: None,
+            price_tier: None,
+        };
+
+        imported.push(ImportedProduct {
+            key,
+            product,
+            entry,
+        });
+    }
+
+    Ok(imported)
+}
+
+/// Derive a config key from a product name, e.g. "100 Coins!" -> "100_coins"
+fn unique_key(
+    name: &str,
+    product_type: &ProductType,
+    roblox_id: u64,
+    used_keys: &mut HashSet<String>,
+) -> String {
+    let mut base = String::new();
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() {
+            base.push(c.to_ascii_lowercase());
+        } else if !base.is_empty() && !base.ends_with('_') {
+            base.push('_');
+        }
+    }
+    let base = base.trim_end_matches('_').to_string();
+
+    let base = if base.is_empty() {
+        match product_type {
+            ProductType::DevProduct => format!("dev_product_{}", roblox_id),
+            ProductType::Gamepass => format!("gamepass_{}", roblox_id),
+        }
+    } else {
+        base
+    };
+
+    let mut key = base.clone();
+    let mut suffix = 2;
+    while used_keys.contains(&key) {
+        key = format!("{}_{}", base, suffix);
+        suffix += 1;
+    }
+
+    used_keys.insert(key.clone());
+    key
+}
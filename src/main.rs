@@ -2,7 +2,13 @@ mod api;
 mod cli;
 mod codegen;
 mod config;
+mod drift;
+mod import;
+mod migrate;
+mod plan;
+mod report;
 mod sync;
+mod validate;
 
 use anyhow::Result;
 use clap::Parser;
@@ -16,14 +22,21 @@ async fn main() -> Result<()> {
 
     match cli.command {
         Commands::Init { force } => cli::init(force)?,
-        Commands::Sync {
-            config,
-            mapping,
-            generate,
+        Commands::Sync(args) if args.dry_run => cli::plan(args.project, args.force, args.orphans)?,
+        Commands::Sync(args) => cli::sync(args).await?,
+        Commands::Plan {
+            project,
             force,
-        } => cli::sync(config, mapping, generate, force).await?,
-        Commands::Generate { config, mapping } => cli::generate(config, mapping)?,
-        Commands::List { config, mapping } => cli::list(config, mapping)?,
+            orphans,
+        } => cli::plan(project, force, orphans)?,
+        Commands::Drift { project } => cli::drift(project).await?,
+        Commands::Import { project } => cli::import(project).await?,
+        Commands::Rename { old, new, project } => cli::rename(old, new, project)?,
+        Commands::Migrate { project } => cli::migrate(project)?,
+        Commands::Validate { project } => cli::validate(project)?,
+        Commands::Generate { project } => cli::generate(project)?,
+        Commands::Schema { output } => cli::schema(output)?,
+        Commands::List { project } => cli::list(project)?,
     }
 
     Ok(())
@@ -0,0 +1,223 @@
+use anyhow::{Context, Result};
+use std::fs;
+use toml_edit::{value, DocumentMut, InlineTable, Item, Table};
+
+use crate::config::{CONFIG_VERSION, DEFAULT_OUTPUT};
+use crate::sync::{self, LOCK_VERSION};
+
+/// Files written before versioning was added are treated as version 1.
+const UNVERSIONED: u32 = 1;
+
+/// An upgrade to the given version, from the version before it.
+type Migration<T> = (u32, fn(&mut T));
+
+/// Config upgrades. They edit the document in place, so the user's comments
+/// and formatting stay.
+const CONFIG_MIGRATIONS: &[Migration<DocumentMut>] = &[(2, name_outputs)];
+
+/// Lock file upgrades.
+const LOCK_MIGRATIONS: &[Migration<toml::Table>] = &[(2, nest_lock_entries)];
+
+/// Version 2 moved lock entries from the top level into `[products]`, making
+/// room for `version` beside them. It also records the product type of
+/// entries written before it was tracked, while `offsale` still tells them
+/// apart: it was only set for gamepasses.
+fn nest_lock_entries(table: &mut toml::Table) {
+    for (_, entry) in table.iter_mut() {
+        let Some(entry) = entry.as_table_mut() else {
+            continue;
+        };
+        if !entry.contains_key("product_type") {
+            let product_type = if entry.contains_key("offsale") {
+                "gamepass"
+            } else {
+                "dev_product"
+            };
+            entry.insert("product_type".to_string(), product_type.into());
+        }
+    }
+
+    let products = std::mem::take(table);
+    table.insert("products".to_string(), toml::Value::Table(products));
+}
+
+/// Version 2 allowed several outputs, each under its own name. The single
+/// `[output]` becomes `[output.main]`, and environment `output` paths point at
+/// it by name.
+fn name_outputs(document: &mut DocumentMut) {
+    let table = document.as_table_mut();
+    if let Some((key, output)) = table.remove_entry("output") {
+        let mut outputs = Table::new();
+        outputs.set_implicit(true);
+        outputs.insert(DEFAULT_OUTPUT, output);
+        table.insert_formatted(&key, Item::Table(outputs));
+    }
+
+    let Some(environments) = table
+        .get_mut("environments")
+        .and_then(Item::as_table_like_mut)
+    else {
+        return;
+    };
+    for (_, environment) in environments.iter_mut() {
+        let Some(output) = environment
+            .as_table_like_mut()
+            .and_then(|environment| environment.get_mut("output"))
+        else {
+            continue;
+        };
+        if let Some(path) = output.as_value().filter(|path| path.is_str()).cloned() {
+            let mut named = InlineTable::new();
+            named.insert(DEFAULT_OUTPUT, path.decorated(" ", " "));
+            *output = value(named);
+        }
+    }
+}
+
+/// Read the format version of a file, failing if it is newer than this build
+/// understands.
+fn file_version(version: Option<i64>, latest: u32, kind: &str, path: &str) -> Result<u32> {
+    let Some(version) = version else {
+        return Ok(UNVERSIONED);
+    };
+    let version = u32::try_from(version)
+        .ok()
+        .filter(|v| *v > 0)
+        .with_context(|| format!("Invalid {} version {} in {}", kind, version, path))?;
+
+    if version > latest {
+        anyhow::bail!(
+            "{} uses {} format version {}, but this version of spearmint (v{}) only supports up to version {}. Please update spearmint.",
+            path,
+            kind,
+            version,
+            env!("CARGO_PKG_VERSION"),
+            latest
+        );
+    }
+
+    Ok(version)
+}
+
+/// The `version` of a lock file. Unversioned lock files kept their entries at
+/// the top level, so a `[version]` table there is a product, not a version.
+fn lock_version(table: &toml::Table) -> Option<i64> {
+    match table.get("version")? {
+        toml::Value::Table(_) => None,
+        version => Some(version.as_integer().unwrap_or(-1)),
+    }
+}
+
+/// Bring a parsed lock file up to the current format, in memory.
+pub fn upgrade_lock(table: &mut toml::Table, path: &str) -> Result<()> {
+    let version = lock_version(table);
+    if version.is_some() {
+        table.remove("version");
+    }
+    let version = file_version(version, LOCK_VERSION, "lock file", path)?;
+
+    for (to, migration) in LOCK_MIGRATIONS {
+        if version < *to {
+            migration(table);
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse a config, returning it with its format version.
+fn parse_config(content: &str, path: &str) -> Result<(DocumentMut, u32)> {
+    let document: DocumentMut = content
+        .parse()
+        .with_context(|| format!("Failed to parse config file: {}", path))?;
+
+    let version = document
+        .get("version")
+        .map(|v| v.as_integer().unwrap_or(-1));
+    let version = file_version(version, CONFIG_VERSION, "config", path)?;
+
+    Ok((document, version))
+}
+
+fn upgrade_document(document: &mut DocumentMut, version: u32) {
+    for (to, migration) in CONFIG_MIGRATIONS {
+        if version < *to {
+            migration(document);
+        }
+    }
+}
+
+/// Bring a config up to the current format, in memory.
+pub fn upgrade_config(content: &str, path: &str) -> Result<toml::Table> {
+    let (mut document, version) = parse_config(content, path)?;
+    upgrade_document(&mut document, version);
+
+    toml::from_str(&document.to_string())
+        .with_context(|| format!("Failed to parse config file: {}", path))
+}
+
+/// Upgrade spearmint.toml in place. Returns the version it was upgraded from,
+/// or None if it was already current.
+pub fn migrate_config(path: &str) -> Result<Option<u32>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read config file: {}", path))?;
+    let (mut document, version) = parse_config(&content, path)?;
+    if document.contains_key("version") && version == CONFIG_VERSION {
+        return Ok(None);
+    }
+
+    upgrade_document(&mut document, version);
+    set_version(&mut document, CONFIG_VERSION);
+
+    fs::write(path, document.to_string())
+        .with_context(|| format!("Failed to write config file: {}", path))?;
+
+    Ok(Some(version))
+}
+
+/// Upgrade a lock file in place. Returns the version it was upgraded from, or
+/// None if it was already current.
+pub fn migrate_lock(path: &str) -> Result<Option<u32>> {
+    let content = fs::read_to_string(path)
+        .with_context(|| format!("Failed to read mapping file: {}", path))?;
+    let table: toml::Table = toml::from_str(&content)
+        .with_context(|| format!("Failed to parse mapping file: {}", path))?;
+
+    let version = lock_version(&table);
+    let versioned = version.is_some();
+    let version = file_version(version, LOCK_VERSION, "lock file", path)?;
+    if versioned && version == LOCK_VERSION {
+        return Ok(None);
+    }
+
+    // Loading applies the migrations, and saving writes the current format
+    let mapping = sync::load_mapping(path)?;
+    sync::save_mapping(&mapping, path)?;
+
+    Ok(Some(version))
+}
+
+/// Put `version` at the top of the document, ahead of any other keys but
+/// after leading comments such as the `#:schema` directive.
+fn set_version(document: &mut DocumentMut, version: u32) {
+    let table = document.as_table_mut();
+
+    let first = table
+        .iter()
+        .find(|(_, item)| item.is_value())
+        .map(|(key, _)| key.to_string());
+    let leading = first.as_deref().and_then(|first| {
+        let mut key = table.key_mut(first)?;
+        let decor = key.leaf_decor_mut();
+        let prefix = decor.prefix().cloned();
+        decor.set_prefix("");
+        prefix
+    });
+
+    table.insert("version", value(i64::from(version)));
+    if let (Some(leading), Some(mut key)) = (leading, table.key_mut("version")) {
+        key.leaf_decor_mut().set_prefix(leading);
+    }
+
+    table.sort_values_by(|a, _, b, _| (b.get() == "version").cmp(&(a.get() == "version")));
+}
@@ -0,0 +1,342 @@
+use sha2::{Digest, Sha256};
+use std::collections::HashSet;
+use std::fs;
+
+use crate::config::{Config, OrphanConfig, OrphanPolicy, Product, ProductType};
+use crate::sync::{Mapping, MappingEntry};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Action {
+    Create,
+    Update,
+    Skip,
+}
+
+impl std::fmt::Display for Action {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Action::Create => write!(f, "create"),
+            Action::Update => write!(f, "update"),
+            Action::Skip => write!(f, "unchanged"),
+        }
+    }
+}
+
+/// A single field that differs between the config and the lock file.
+#[derive(Debug, Clone)]
+pub struct FieldChange {
+    pub field: &'static str,
+    pub old: Option<String>,
+    pub new: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct PlannedProduct<'a> {
+    pub key: &'a str,
+    pub product: &'a Product,
+    pub roblox_id: Option<u64>,
+    pub action: Action,
+    pub changes: Vec<FieldChange>,
+    /// Icon to upload, if the image is new or has changed
+    pub icon_path: Option<String>,
+}
+
+/// What to do with a lock entry whose key is no longer in the config.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OrphanAction {
+    Keep,
+    Offsale,
+    Archive { name: String },
+    Forget,
+}
+
+impl std::fmt::Display for OrphanAction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OrphanAction::Keep => write!(f, "keep"),
+            OrphanAction::Offsale => write!(f, "take off sale"),
+            OrphanAction::Archive { name } => write!(f, "archive as {:?}", name),
+            OrphanAction::Forget => write!(f, "forget"),
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PlannedOrphan {
+    pub key: String,
+    pub product_type: ProductType,
+    pub roblox_id: u64,
+    pub action: OrphanAction,
+}
+
+#[derive(Debug)]
+pub struct Plan<'a> {
+    pub universe_id: u64,
+    pub products: Vec<PlannedProduct<'a>>,
+    pub orphans: Vec<PlannedOrphan>,
+}
+
+impl Plan<'_> {
+    pub fn count(&self, action: Action) -> usize {
+        self.products.iter().filter(|p| p.action == action).count()
+    }
+
+    pub fn has_changes(&self) -> bool {
+        self.products.iter().any(|p| p.action != Action::Skip)
+            || self.orphans.iter().any(|o| o.action != OrphanAction::Keep)
+    }
+
+    pub fn print(&self) {
+        println!("Plan for universe {}:\n", self.universe_id);
+
+        for planned in &self.products {
+            let symbol = match planned.action {
+                Action::Create => "+",
+                Action::Update => "~",
+                Action::Skip => continue,
+            };
+
+            match planned.roblox_id {
+                Some(id) => println!(
+                    "  {} {} - {} ({}, ID: {})",
+                    symbol, planned.product.product_type, planned.key, planned.action, id
+                ),
+                None => println!(
+                    "  {} {} - {} ({})",
+                    symbol, planned.product.product_type, planned.key, planned.action
+                ),
+            }
+
+            for change in &planned.changes {
+                let tier = match planned.product.price_tier {
+                    Some(ref tier) if change.field == "price" => format!(" ({})", tier),
+                    _ => String::new(),
+                };
+                println!(
+                    "      {}: {} -> {}{}",
+                    change.field,
+                    change.old.as_deref().unwrap_or("(none)"),
+                    change.new.as_deref().unwrap_or("(none)"),
+                    tier
+                );
+            }
+        }
+
+        for orphan in &self.orphans {
+            let symbol = match orphan.action {
+                OrphanAction::Keep => "!",
+                _ => "-",
+            };
+            println!(
+                "  {} {} - {} (orphaned, {}, ID: {})",
+                symbol, orphan.product_type, orphan.key, orphan.action, orphan.roblox_id
+            );
+        }
+
+        if !self.has_changes() {
+            println!("  No changes. All products are up to date.");
+        }
+
+        println!(
+            "\nPlan: {} to create, {} to update, {} unchanged, {} orphaned",
+            self.count(Action::Create),
+            self.count(Action::Update),
+            self.count(Action::Skip),
+            self.orphans.len()
+        );
+    }
+}
+
+/// Decide what sync would do for every product, without making any API calls.
+pub fn build<'a>(
+    config: &'a Config,
+    mapping: &Mapping,
+    force: bool,
+    orphans: &OrphanConfig,
+) -> Plan<'a> {
+    let products = config
+        .products
+        .iter()
+        .map(|(key, product)| plan_product(key, product, mapping.get(key), force))
+        .collect();
+
+    // A lock entry under an old key may still be the product a config entry
+    // points at, through `product_id` or a key moved by hand. Those are live
+    // products, not orphans.
+    let referenced: HashSet<(ProductType, u64)> = config
+        .products
+        .iter()
+        .filter_map(|(key, product)| {
+            let id = product
+                .product_id
+                .or_else(|| mapping.get(key).map(|entry| entry.roblox_id))?;
+            Some((product.product_type.clone(), id))
+        })
+        .collect();
+
+    let mut orphans: Vec<PlannedOrphan> = mapping
+        .iter()
+        .filter(|(key, _)| !config.products.contains_key(*key))
+        .filter(|(_, entry)| !referenced.contains(&(entry.product_type(), entry.roblox_id)))
+        .filter_map(|(key, entry)| plan_orphan(key, entry, orphans))
+        .collect();
+    orphans.sort_by(|a, b| a.key.cmp(&b.key));
+
+    Plan {
+        universe_id: config.universe_id,
+        products,
+        orphans,
+    }
+}
+
+fn plan_orphan(key: &str, entry: &MappingEntry, orphans: &OrphanConfig) -> Option<PlannedOrphan> {
+    let already_offsale = entry.offsale == Some(true);
+
+    let action = match orphans.policy {
+        OrphanPolicy::Keep => OrphanAction::Keep,
+        OrphanPolicy::Forget => OrphanAction::Forget,
+        OrphanPolicy::Offsale if already_offsale => return None,
+        OrphanPolicy::Offsale => OrphanAction::Offsale,
+        OrphanPolicy::Archive => {
+            let name = entry.name.as_deref().unwrap_or(key);
+            if already_offsale && name.starts_with(&orphans.archive_prefix) {
+                return None;
+            }
+            let name = if name.starts_with(&orphans.archive_prefix) {
+                name.to_string()
+            } else {
+                format!("{}{}", orphans.archive_prefix, name)
+            };
+            OrphanAction::Archive { name }
+        }
+    };
+
+    Some(PlannedOrphan {
+        key: key.to_string(),
+        product_type: entry.product_type(),
+        roblox_id: entry.roblox_id,
+        action,
+    })
+}
+
+fn plan_product<'a>(
+    key: &'a str,
+    product: &'a Product,
+    entry: Option<&MappingEntry>,
+    force: bool,
+) -> PlannedProduct<'a> {
+    let roblox_id = product.product_id.or_else(|| entry.map(|e| e.roblox_id));
+
+    let Some(roblox_id) = roblox_id else {
+        return PlannedProduct {
+            key,
+            product,
+            roblox_id: None,
+            action: Action::Create,
+            changes: field_changes(product, None),
+            icon_path: product.image.clone(),
+        };
+    };
+
+    let changes = field_changes(product, entry);
+
+    // Only skip when the lock file proves the product is up to date
+    if !force && entry.is_some() && changes.is_empty() {
+        return PlannedProduct {
+            key,
+            product,
+            roblox_id: Some(roblox_id),
+            action: Action::Skip,
+            changes,
+            icon_path: None,
+        };
+    }
+
+    // Include icon if it has changed or if force is enabled
+    let icon_path = product.image.as_ref().and_then(|image| {
+        let image_changed = changes.iter().any(|c| c.field == "image");
+        (force || image_changed).then(|| image.clone())
+    });
+
+    PlannedProduct {
+        key,
+        product,
+        roblox_id: Some(roblox_id),
+        action: Action::Update,
+        changes,
+        icon_path,
+    }
+}
+
+fn field_changes(product: &Product, entry: Option<&MappingEntry>) -> Vec<FieldChange> {
+    let mut changes = Vec::new();
+
+    let mut push = |field, old: Option<String>, new: Option<String>| {
+        if old != new {
+            changes.push(FieldChange { field, old, new });
+        }
+    };
+
+    push(
+        "name",
+        entry
+            .and_then(|e| e.name.as_ref())
+            .map(|n| format!("{:?}", n)),
+        Some(format!("{:?}", product.name)),
+    );
+    push(
+        "price",
+        entry.and_then(|e| e.price).map(|p| p.to_string()),
+        Some(product.price.to_string()),
+    );
+    push(
+        "description",
+        entry
+            .and_then(|e| e.description.as_ref())
+            .map(|d| format!("{:?}", d)),
+        product.description.as_ref().map(|d| format!("{:?}", d)),
+    );
+    push(
+        "image",
+        entry.and_then(|e| e.image_hash.clone()),
+        image_hash(product),
+    );
+    if product.product_type == ProductType::Gamepass {
+        push(
+            "offsale",
+            entry.and_then(|e| e.offsale).map(|o| o.to_string()),
+            Some(product.offsale.to_string()),
+        );
+    }
+    // Unset flags are left alone on Roblox, so they never count as changes
+    if let Some(regional_pricing) = product.regional_pricing {
+        push(
+            "regional_pricing",
+            entry
+                .and_then(|e| e.regional_pricing)
+                .map(|r| r.to_string()),
+            Some(regional_pricing.to_string()),
+        );
+    }
+    if let Some(store_page) = product.store_page {
+        if product.product_type == ProductType::DevProduct {
+            push(
+                "store_page",
+                entry.and_then(|e| e.store_page).map(|s| s.to_string()),
+                Some(store_page.to_string()),
+            );
+        }
+    }
+
+    changes
+}
+
+fn hash_file(path: &str) -> Option<String> {
+    let bytes = fs::read(path).ok()?;
+    let hash = Sha256::digest(&bytes);
+    Some(hex::encode(hash))
+}
+
+pub fn image_hash(product: &Product) -> Option<String> {
+    product.image.as_deref().and_then(hash_file)
+}
@@ -0,0 +1,226 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::fmt::Write as _;
+use std::fs;
+use std::path::Path;
+use std::time::Duration;
+
+use crate::api::ApiError;
+use crate::config::ProductType;
+
+/// What happened to a single product during sync.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Created,
+    Updated,
+    Unchanged,
+    Failed,
+    /// Orphan left in place
+    Kept,
+    /// Orphan taken off sale
+    Offsale,
+    /// Orphan renamed and taken off sale
+    Archived,
+    /// Orphan dropped from the lock file
+    Forgotten,
+}
+
+impl std::fmt::Display for Outcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Outcome::Created => write!(f, "created"),
+            Outcome::Updated => write!(f, "updated"),
+            Outcome::Unchanged => write!(f, "skipped"),
+            Outcome::Failed => write!(f, "ERROR"),
+            Outcome::Kept => write!(f, "kept"),
+            Outcome::Offsale => write!(f, "offsale"),
+            Outcome::Archived => write!(f, "archived"),
+            Outcome::Forgotten => write!(f, "forgotten"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorKind {
+    /// Still rate limited after every retry
+    RateLimited,
+    /// Open Cloud rejected the request
+    Api,
+    /// The request never got a response
+    Network,
+    /// A local file (such as an icon) could not be read
+    Io,
+    Other,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncError {
+    pub kind: ErrorKind,
+    /// HTTP status, for API errors
+    pub status: Option<u16>,
+    pub message: String,
+}
+
+impl From<&anyhow::Error> for SyncError {
+    fn from(error: &anyhow::Error) -> Self {
+        let (kind, status) = if let Some(api) = error.downcast_ref::<ApiError>() {
+            let kind = if api.status == 429 {
+                ErrorKind::RateLimited
+            } else {
+                ErrorKind::Api
+            };
+            (kind, Some(api.status.as_u16()))
+        } else if error.downcast_ref::<reqwest::Error>().is_some() {
+            (ErrorKind::Network, None)
+        } else if error.chain().any(|e| e.is::<std::io::Error>()) {
+            (ErrorKind::Io, None)
+        } else {
+            (ErrorKind::Other, None)
+        };
+
+        Self {
+            kind,
+            status,
+            message: format!("{:#}", error),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ProductReport {
+    pub key: String,
+    pub product_type: ProductType,
+    pub roblox_id: Option<u64>,
+    pub outcome: Outcome,
+    pub changed_fields: Vec<&'static str>,
+    pub icon_uploaded: bool,
+    #[serde(rename = "duration_ms", serialize_with = "serialize_millis")]
+    pub duration: Duration,
+    pub error: Option<SyncError>,
+}
+
+impl ProductReport {
+    /// Print the one-line progress message for this product
+    pub fn print(&self) {
+        match &self.error {
+            Some(error) => println!(
+                "[{}] {} - {}: {}",
+                self.outcome, self.product_type, self.key, error.message
+            ),
+            None => println!("[{}] {} - {}", self.outcome, self.product_type, self.key),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SyncReport {
+    pub universe_id: u64,
+    pub products: Vec<ProductReport>,
+    pub orphans: Vec<ProductReport>,
+    #[serde(rename = "duration_ms", serialize_with = "serialize_millis")]
+    pub duration: Duration,
+}
+
+impl SyncReport {
+    pub fn count(&self, outcome: Outcome) -> usize {
+        self.products
+            .iter()
+            .chain(&self.orphans)
+            .filter(|p| p.outcome == outcome)
+            .count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.count(Outcome::Failed)
+    }
+
+    pub fn print_summary(&self) {
+        println!(
+            "\nSummary: {} created, {} updated, {} unchanged, {} failed",
+            self.count(Outcome::Created),
+            self.count(Outcome::Updated),
+            self.count(Outcome::Unchanged),
+            self.failed()
+        );
+
+        if !self.orphans.is_empty() {
+            println!(
+                "Orphans: {} kept, {} taken off sale, {} archived, {} forgotten",
+                self.count(Outcome::Kept),
+                self.count(Outcome::Offsale),
+                self.count(Outcome::Archived),
+                self.count(Outcome::Forgotten)
+            );
+        }
+    }
+
+    pub fn write_json(&self, path: &str) -> Result<()> {
+        let path = Path::new(path);
+
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Render the report as a Markdown table, for CI job summaries
+    pub fn to_markdown(&self) -> String {
+        let mut output = String::new();
+
+        let _ = writeln!(
+            output,
+            "### spearmint sync (universe {})\n",
+            self.universe_id
+        );
+        let _ = writeln!(
+            output,
+            "{} created, {} updated, {} unchanged, {} failed\n",
+            self.count(Outcome::Created),
+            self.count(Outcome::Updated),
+            self.count(Outcome::Unchanged),
+            self.failed()
+        );
+
+        let changed: Vec<&ProductReport> = self
+            .products
+            .iter()
+            .chain(&self.orphans)
+            .filter(|p| !matches!(p.outcome, Outcome::Unchanged | Outcome::Kept))
+            .collect();
+
+        if changed.is_empty() {
+            return output;
+        }
+
+        output.push_str("| Product | Type | ID | Result | Changes |\n");
+        output.push_str("|---|---|---|---|---|\n");
+        for product in changed {
+            let details = match &product.error {
+                Some(error) => error.message.replace('|', "\\|"),
+                None => product.changed_fields.join(", "),
+            };
+            let _ = writeln!(
+                output,
+                "| `{}` | {} | {} | {} | {} |",
+                product.key,
+                product.product_type,
+                product
+                    .roblox_id
+                    .map(|id| id.to_string())
+                    .unwrap_or_default(),
+                product.outcome,
+                details
+            );
+        }
+
+        output
+    }
+}
+
+fn serialize_millis<S: serde::Serializer>(duration: &Duration, s: S) -> Result<S::Ok, S::Error> {
+    s.serialize_u64(duration.as_millis() as u64)
+}
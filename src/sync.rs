@@ -1,14 +1,25 @@
 use anyhow::{Context, Result};
+use futures::stream::{self, StreamExt};
 use serde::{Deserialize, Serialize};
-use sha2::{Digest, Sha256};
-use std::collections::HashMap;
+use std::collections::BTreeMap;
 use std::fs;
+use std::io::Write;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Instant;
 
-use crate::api::{Client, CreateDevProductRequest, UpdateDevProductRequest, UpdateGamepassRequest};
+use crate::api::{
+    Client, CreateDevProductRequest, CreateGamepassRequest, UpdateDevProductRequest,
+    UpdateGamepassRequest,
+};
 use crate::config::{Config, Product, ProductType};
+use crate::migrate;
+use crate::plan::{image_hash, Action, OrphanAction, Plan, PlannedOrphan, PlannedProduct};
+use crate::report::{Outcome, ProductReport, SyncError, SyncReport};
 
 pub const DEFAULT_MAPPING_PATH: &str = "spearmint.lock.toml";
+/// Format version of the lock file written by this build
+pub const LOCK_VERSION: u32 = 2;
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct MappingEntry {
@@ -18,29 +29,88 @@ pub struct MappingEntry {
     pub description: Option<String>,
     pub image_hash: Option<String>,
     pub offsale: Option<bool>,
+    pub product_type: Option<ProductType>,
+    pub regional_pricing: Option<bool>,
+    pub store_page: Option<bool>,
 }
 
-pub type Mapping = HashMap<String, MappingEntry>;
+impl MappingEntry {
+    /// The product type recorded in the lock file. Entries written before the
+    /// type was recorded only have `offsale` set for gamepasses.
+    pub fn product_type(&self) -> ProductType {
+        match (&self.product_type, self.offsale) {
+            (Some(product_type), _) => product_type.clone(),
+            (None, Some(_)) => ProductType::Gamepass,
+            (None, None) => ProductType::DevProduct,
+        }
+    }
+}
 
-#[derive(Debug)]
-pub struct SyncResult {
-    pub action: String,
-    pub error: Option<String>,
+/// Lock entries by product key, kept sorted so the lock file is stable
+pub type Mapping = BTreeMap<String, MappingEntry>;
+
+/// The lock file as written to disk.
+#[derive(Serialize)]
+struct LockFile<'a> {
+    version: u32,
+    products: &'a Mapping,
 }
 
 pub fn load_mapping(mapping_path: &str) -> Result<Mapping> {
     let path = Path::new(mapping_path);
 
     if !path.exists() {
-        return Ok(HashMap::new());
+        return Ok(BTreeMap::new());
     }
 
     let content = fs::read_to_string(path)
         .with_context(|| format!("Failed to read mapping file: {}", path.display()))?;
 
-    toml::from_str(&content).with_context(|| "Failed to parse mapping file")
+    let mut table: toml::Table =
+        toml::from_str(&content).with_context(|| "Failed to parse mapping file")?;
+
+    // Older lock files are upgraded as they are read, and written back in the
+    // current format on the next save
+    migrate::upgrade_lock(&mut table, mapping_path)?;
+    let products = table
+        .remove("products")
+        .unwrap_or_else(|| toml::Value::Table(toml::Table::new()));
+
+    products
+        .try_into()
+        .with_context(|| "Failed to parse mapping file")
 }
 
+/// Move lock entries of renamed products from a previous key to the current
+/// one, returning each `(old, new)` pair that was moved.
+pub fn apply_renames(config: &Config, mapping: &mut Mapping) -> Vec<(String, String)> {
+    let mut renamed = Vec::new();
+
+    for (key, product) in &config.products {
+        if mapping.contains_key(key) {
+            continue;
+        }
+
+        let previous = product
+            .previous_keys
+            .iter()
+            .find(|previous| mapping.contains_key(*previous));
+
+        if let Some(previous) = previous {
+            if let Some(entry) = mapping.remove(previous) {
+                mapping.insert(key.clone(), entry);
+                renamed.push((previous.clone(), key.clone()));
+            }
+        }
+    }
+
+    renamed.sort();
+    renamed
+}
+
+/// Write the lock file atomically: the content goes to a temporary file in the
+/// same directory which is then renamed over the lock file, so an interrupted
+/// write never leaves a truncated lock behind.
 pub fn save_mapping(mapping: &Mapping, mapping_path: &str) -> Result<()> {
     let path = Path::new(mapping_path);
 
@@ -48,292 +118,362 @@ pub fn save_mapping(mapping: &Mapping, mapping_path: &str) -> Result<()> {
         fs::create_dir_all(parent)?;
     }
 
-    let content = toml::to_string_pretty(mapping)?;
-    fs::write(path, content)?;
+    let content = toml::to_string_pretty(&LockFile {
+        version: LOCK_VERSION,
+        products: mapping,
+    })?;
+
+    let tmp_path = path.with_file_name(format!(
+        "{}.tmp",
+        path.file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or(DEFAULT_MAPPING_PATH)
+    ));
+
+    let written = write_synced(&tmp_path, &content).and_then(|()| {
+        fs::rename(&tmp_path, path)
+            .with_context(|| format!("Failed to write mapping file: {}", path.display()))
+    });
+    if written.is_err() {
+        // Don't leave a partial lock file behind
+        let _ = fs::remove_file(&tmp_path);
+    }
 
+    written
+}
+
+/// Write a file and flush it to disk.
+fn write_synced(path: &Path, content: &str) -> Result<()> {
+    let mut file =
+        fs::File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+    file.write_all(content.as_bytes())?;
+    file.sync_all()?;
     Ok(())
 }
 
+/// Carry out the plan: sync every product, then apply the orphan policy.
+///
+/// Products run up to `jobs` at once. Workers only talk to Roblox; lock
+/// updates and output happen here as each product finishes, so the mapping is
+/// only ever touched from one place. The lock file is saved after every
+/// change, so an interrupted sync never loses track of products that already
+/// exist on Roblox. If a save fails, products already in flight still finish
+/// and are kept in `mapping`, but no new ones are started.
 pub async fn sync_all_products(
     client: &Client,
-    config: &Config,
+    plan: &Plan<'_>,
     mapping: &mut Mapping,
-    force: bool,
-) -> Result<Vec<SyncResult>> {
-    let mut results = Vec::new();
-
-    for (key, product) in &config.products {
-        let result = sync_product(client, config.universe_id, key, product, mapping, force).await;
+    mapping_path: &str,
+    jobs: usize,
+) -> Result<SyncReport> {
+    let started = Instant::now();
+    let mut products = Vec::new();
+    let save_failed = &AtomicBool::new(false);
+    let mut save_error = None;
+    let mut unsaved = Vec::new();
+
+    let mut pending = stream::iter(plan.products.iter().enumerate())
+        .map(|(index, planned)| async move {
+            let started = Instant::now();
+            let result = if save_failed.load(Ordering::Relaxed) {
+                Err(anyhow::anyhow!(
+                    "Skipped because the lock file could not be saved"
+                ))
+            } else {
+                sync_product(client, plan.universe_id, planned).await
+            };
+            (index, planned, result, started.elapsed())
+        })
+        .buffer_unordered(jobs.max(1));
+
+    while let Some((index, planned, result, duration)) = pending.next().await {
+        let mut report = ProductReport {
+            key: planned.key.to_string(),
+            product_type: planned.product.product_type.clone(),
+            roblox_id: planned.roblox_id,
+            outcome: Outcome::Unchanged,
+            changed_fields: planned.changes.iter().map(|c| c.field).collect(),
+            icon_uploaded: false,
+            duration,
+            error: None,
+        };
 
         match result {
-            Ok(action) => {
-                println!("[{}] {} - {}", action, product.product_type, key);
-                results.push(SyncResult {
-                    action,
-                    error: None,
-                });
+            Ok(entry) => {
+                report.outcome = match planned.action {
+                    Action::Create => Outcome::Created,
+                    Action::Update => Outcome::Updated,
+                    Action::Skip => Outcome::Unchanged,
+                };
+                if let Some(entry) = entry {
+                    report.roblox_id = Some(entry.roblox_id);
+                    report.icon_uploaded = planned.icon_path.is_some();
+                    unsaved.push(format!("{} ({})", planned.key, entry.roblox_id));
+                    mapping.insert(planned.key.to_string(), entry);
+
+                    // Keep going so in-flight products land in the mapping,
+                    // and retry the save as each one finishes
+                    match save_mapping(mapping, mapping_path) {
+                        Ok(()) => {
+                            unsaved.clear();
+                            save_error = None;
+                        }
+                        Err(e) => {
+                            save_failed.store(true, Ordering::Relaxed);
+                            save_error = Some(e);
+                        }
+                    }
+                }
             }
             Err(e) => {
-                println!("[ERROR] {} - {}: {}", product.product_type, key, e);
-                results.push(SyncResult {
-                    action: "error".to_string(),
-                    error: Some(e.to_string()),
-                });
+                report.outcome = Outcome::Failed;
+                report.error = Some(SyncError::from(&e));
             }
         }
+
+        report.print();
+        products.push((index, report));
     }
 
-    Ok(results)
-}
+    if let Some(e) = save_error {
+        return Err(e.context(format!(
+            "Failed to save {}. These products exist on Roblox but are missing from it: {}",
+            mapping_path,
+            unsaved.join(", ")
+        )));
+    }
 
-async fn sync_product(
-    client: &Client,
-    universe_id: u64,
-    key: &str,
-    product: &Product,
-    mapping: &mut Mapping,
-    force: bool,
-) -> Result<String> {
-    let existing_id = product
-        .product_id
-        .or_else(|| mapping.get(key).map(|m| m.roblox_id));
+    // Products finish in any order; report them in config order
+    products.sort_by_key(|(index, _)| *index);
+    let products = products.into_iter().map(|(_, report)| report).collect();
 
-    match product.product_type {
-        ProductType::DevProduct => {
-            sync_dev_product(
-                client,
-                universe_id,
-                key,
-                product,
-                existing_id,
-                mapping,
-                force,
-            )
-            .await
-        }
-        ProductType::Gamepass => {
-            sync_gamepass(
-                client,
-                universe_id,
-                key,
-                product,
-                existing_id,
-                mapping,
-                force,
-            )
-            .await
-        }
-    }
-}
+    let orphans = sync_orphans(client, plan, mapping, mapping_path).await?;
 
-fn hash_file(path: &str) -> Option<String> {
-    let bytes = fs::read(path).ok()?;
-    let hash = Sha256::digest(&bytes);
-    Some(hex::encode(hash))
+    Ok(SyncReport {
+        universe_id: plan.universe_id,
+        products,
+        orphans,
+        duration: started.elapsed(),
+    })
 }
 
-fn image_hash(product: &Product) -> Option<String> {
-    product.image.as_deref().and_then(hash_file)
-}
+/// Apply the orphan policy to lock entries whose key was removed from the config.
+async fn sync_orphans(
+    client: &Client,
+    plan: &Plan<'_>,
+    mapping: &mut Mapping,
+    mapping_path: &str,
+) -> Result<Vec<ProductReport>> {
+    let mut reports = Vec::new();
+
+    for orphan in &plan.orphans {
+        let started = Instant::now();
+        let result = sync_orphan(client, plan.universe_id, orphan, mapping).await;
+
+        let mut report = ProductReport {
+            key: orphan.key.clone(),
+            product_type: orphan.product_type.clone(),
+            roblox_id: Some(orphan.roblox_id),
+            outcome: Outcome::Kept,
+            changed_fields: Vec::new(),
+            icon_uploaded: false,
+            duration: started.elapsed(),
+            error: None,
+        };
 
-fn config_changed(product: &Product, entry: &MappingEntry) -> bool {
-    entry.name.as_deref() != Some(&product.name)
-        || entry.price != Some(product.price)
-        || entry.description != product.description
-        || image_hash(product) != entry.image_hash
-        || (product.product_type == ProductType::Gamepass && entry.offsale != Some(product.offsale))
-}
+        match result {
+            Ok(outcome) => {
+                report.outcome = outcome;
+                report.changed_fields = match orphan.action {
+                    OrphanAction::Offsale => vec!["offsale"],
+                    OrphanAction::Archive { .. } => vec!["name", "offsale"],
+                    OrphanAction::Keep | OrphanAction::Forget => Vec::new(),
+                };
+                if orphan.action != OrphanAction::Keep {
+                    save_mapping(mapping, mapping_path)?;
+                }
+            }
+            Err(e) => {
+                report.outcome = Outcome::Failed;
+                report.error = Some(SyncError::from(&e));
+            }
+        }
 
-fn update_mapping_entry(entry: &mut MappingEntry, product: &Product) {
-    entry.name = Some(product.name.clone());
-    entry.price = Some(product.price);
-    entry.description = product.description.clone();
-    entry.image_hash = image_hash(product);
-    if product.product_type == ProductType::Gamepass {
-        entry.offsale = Some(product.offsale);
+        report.print();
+        reports.push(report);
     }
+
+    Ok(reports)
 }
 
-async fn sync_dev_product(
+async fn sync_orphan(
     client: &Client,
     universe_id: u64,
-    key: &str,
-    product: &Product,
-    existing_id: Option<u64>,
+    orphan: &PlannedOrphan,
     mapping: &mut Mapping,
-    force: bool,
-) -> Result<String> {
-    match existing_id {
-        Some(id) => {
-            // Check locally if the config has changed since last sync (skip if force)
-            if !force {
-                if let Some(entry) = mapping.get(key) {
-                    if !config_changed(product, entry) {
-                        return Ok("skipped".to_string());
-                    }
-                }
-            }
-
-            // Include icon if it has changed or if force is enabled
-            let icon_path = if let Some(ref image) = product.image {
-                if force {
-                    Some(image.clone())
-                } else {
-                    let new_hash = hash_file(image);
-                    let old_hash = mapping.get(key).and_then(|e| e.image_hash.clone());
-                    if new_hash != old_hash {
-                        Some(image.clone())
-                    } else {
-                        None
-                    }
-                }
-            } else {
-                None
-            };
+) -> Result<Outcome> {
+    let name = match &orphan.action {
+        OrphanAction::Keep => return Ok(Outcome::Kept),
+        OrphanAction::Forget => {
+            mapping.remove(&orphan.key);
+            return Ok(Outcome::Forgotten);
+        }
+        OrphanAction::Offsale => None,
+        OrphanAction::Archive { name } => Some(name.clone()),
+    };
 
+    match orphan.product_type {
+        ProductType::DevProduct => {
             client
                 .update_dev_product(
                     universe_id,
-                    id,
+                    orphan.roblox_id,
                     UpdateDevProductRequest {
-                        name: Some(product.name.clone()),
-                        price: Some(product.price),
-                        description: product.description.clone(),
-                        icon_path,
+                        name: name.clone(),
+                        price: None,
+                        description: None,
+                        icon_path: None,
+                        is_for_sale: Some(false),
+                        regional_pricing: None,
+                        store_page: None,
                     },
                 )
-                .await?;
-
-            let entry = mapping.entry(key.to_string()).or_insert(MappingEntry {
-                roblox_id: id,
-                name: None,
-                price: None,
-                description: None,
-                image_hash: None,
-                offsale: None,
-            });
-            update_mapping_entry(entry, product);
-
-            Ok("updated".to_string())
+                .await?
         }
-        None => {
-            let response = client
-                .create_dev_product(
+        ProductType::Gamepass => {
+            client
+                .update_gamepass(
                     universe_id,
-                    CreateDevProductRequest {
-                        name: product.name.clone(),
-                        price: product.price,
-                        description: product.description.clone(),
-                        icon_path: product.image.clone(),
+                    orphan.roblox_id,
+                    UpdateGamepassRequest {
+                        name: name.clone(),
+                        price: None,
+                        description: None,
+                        icon_path: None,
+                        is_for_sale: Some(false),
+                        regional_pricing: None,
                     },
                 )
-                .await?;
-
-            mapping.insert(
-                key.to_string(),
-                MappingEntry {
-                    roblox_id: response.product_id,
-                    name: Some(product.name.clone()),
-                    price: Some(product.price),
-                    description: product.description.clone(),
-                    image_hash: image_hash(product),
-                    offsale: None,
-                },
-            );
-
-            Ok("created".to_string())
+                .await?
         }
     }
+
+    if let Some(entry) = mapping.get_mut(&orphan.key) {
+        // Offsale alone would make an old entry without a type look like a
+        // gamepass
+        entry.product_type = Some(orphan.product_type.clone());
+        entry.offsale = Some(true);
+        if name.is_some() {
+            entry.name = name;
+        }
+    }
+
+    Ok(if orphan.action == OrphanAction::Offsale {
+        Outcome::Offsale
+    } else {
+        Outcome::Archived
+    })
 }
 
-async fn sync_gamepass(
+/// Carry out a planned action, returning the new lock entry if anything changed.
+async fn sync_product(
     client: &Client,
     universe_id: u64,
-    key: &str,
-    product: &Product,
-    existing_id: Option<u64>,
-    mapping: &mut Mapping,
-    force: bool,
-) -> Result<String> {
-    match existing_id {
-        Some(id) => {
-            // Check locally if the config has changed since last sync (skip if force)
-            if !force {
-                if let Some(entry) = mapping.get(key) {
-                    if !config_changed(product, entry) {
-                        return Ok("skipped".to_string());
-                    }
+    planned: &PlannedProduct<'_>,
+) -> Result<Option<MappingEntry>> {
+    let product = planned.product;
+
+    let roblox_id = match (planned.action, planned.roblox_id) {
+        (Action::Skip, _) => return Ok(None),
+        (Action::Update, Some(id)) => {
+            match product.product_type {
+                ProductType::DevProduct => {
+                    client
+                        .update_dev_product(
+                            universe_id,
+                            id,
+                            UpdateDevProductRequest {
+                                name: Some(product.name.clone()),
+                                price: Some(product.price),
+                                description: product.description.clone(),
+                                icon_path: planned.icon_path.clone(),
+                                is_for_sale: None,
+                                regional_pricing: product.regional_pricing,
+                                store_page: product.store_page,
+                            },
+                        )
+                        .await?
                 }
+                ProductType::Gamepass => {
+                    client
+                        .update_gamepass(
+                            universe_id,
+                            id,
+                            UpdateGamepassRequest {
+                                name: Some(product.name.clone()),
+                                price: Some(product.price),
+                                description: product.description.clone(),
+                                icon_path: planned.icon_path.clone(),
+                                is_for_sale: Some(!product.offsale),
+                                regional_pricing: product.regional_pricing,
+                            },
+                        )
+                        .await?
+                }
+            }
+            id
+        }
+        _ => match product.product_type {
+            ProductType::DevProduct => {
+                client
+                    .create_dev_product(
+                        universe_id,
+                        CreateDevProductRequest {
+                            name: product.name.clone(),
+                            price: product.price,
+                            description: product.description.clone(),
+                            icon_path: planned.icon_path.clone(),
+                            regional_pricing: product.regional_pricing,
+                            store_page: product.store_page,
+                        },
+                    )
+                    .await?
+                    .product_id
             }
+            ProductType::Gamepass => {
+                client
+                    .create_gamepass(
+                        universe_id,
+                        CreateGamepassRequest {
+                            name: product.name.clone(),
+                            price: product.price,
+                            description: product.description.clone(),
+                            icon_path: planned.icon_path.clone(),
+                            is_for_sale: !product.offsale,
+                            regional_pricing: product.regional_pricing,
+                        },
+                    )
+                    .await?
+                    .game_pass_id
+            }
+        },
+    };
 
-            // Include icon if it has changed or if force is enabled
-            let icon_path = if let Some(ref image) = product.image {
-                if force {
-                    Some(image.clone())
-                } else {
-                    let new_hash = hash_file(image);
-                    let old_hash = mapping.get(key).and_then(|e| e.image_hash.clone());
-                    if new_hash != old_hash {
-                        Some(image.clone())
-                    } else {
-                        None
-                    }
-                }
-            } else {
-                None
-            };
+    Ok(Some(mapping_entry(roblox_id, product)))
+}
 
-            client
-                .update_gamepass(
-                    universe_id,
-                    id,
-                    UpdateGamepassRequest {
-                        name: Some(product.name.clone()),
-                        price: Some(product.price),
-                        description: product.description.clone(),
-                        icon_path,
-                        is_for_sale: Some(!product.offsale),
-                    },
-                )
-                .await?;
-
-            let entry = mapping.entry(key.to_string()).or_insert(MappingEntry {
-                roblox_id: id,
-                name: None,
-                price: None,
-                description: None,
-                image_hash: None,
-                offsale: None,
-            });
-            update_mapping_entry(entry, product);
-
-            Ok("updated".to_string())
-        }
-        None => {
-            let response = client
-                .create_gamepass(
-                    universe_id,
-                    product.name.clone(),
-                    product.price,
-                    product.description.clone(),
-                    product.image.clone(),
-                    !product.offsale,
-                )
-                .await?;
-
-            mapping.insert(
-                key.to_string(),
-                MappingEntry {
-                    roblox_id: response.game_pass_id,
-                    name: Some(product.name.clone()),
-                    price: Some(product.price),
-                    description: product.description.clone(),
-                    image_hash: image_hash(product),
-                    offsale: Some(product.offsale),
-                },
-            );
-
-            Ok("created".to_string())
-        }
+fn mapping_entry(roblox_id: u64, product: &Product) -> MappingEntry {
+    MappingEntry {
+        roblox_id,
+        name: Some(product.name.clone()),
+        price: Some(product.price),
+        description: product.description.clone(),
+        image_hash: image_hash(product),
+        offsale: (product.product_type == ProductType::Gamepass).then_some(product.offsale),
+        product_type: Some(product.product_type.clone()),
+        regional_pricing: product.regional_pricing,
+        store_page: product
+            .store_page
+            .filter(|_| product.product_type == ProductType::DevProduct),
     }
 }
@@ -0,0 +1,258 @@
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+use toml_edit::ImDocument;
+
+use crate::config::{Config, Product, ProductType};
+
+/// Longest product name Roblox accepts
+pub const MAX_NAME_LENGTH: usize = 50;
+/// Longest product description Roblox accepts
+pub const MAX_DESCRIPTION_LENGTH: usize = 1000;
+pub const MIN_PRICE: u64 = 1;
+pub const MAX_PRICE: u64 = 1_000_000_000;
+/// Image formats Roblox accepts for product icons
+pub const IMAGE_FORMATS: [&str; 4] = ["png", "jpg", "jpeg", "bmp"];
+
+/// A problem with the config, located at the key that caused it.
+#[derive(Debug)]
+pub struct Diagnostic {
+    pub file: String,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.line, self.column, self.message
+        )
+    }
+}
+
+/// Check every product against the limits Open Cloud enforces, so bad values
+/// are caught before any request is made.
+///
+/// `env` is the environment the config was resolved for, so problems coming
+/// from its overrides point at the override.
+pub fn check(config: &Config, config_path: &str, env: Option<&str>) -> Result<Vec<Diagnostic>> {
+    let mut sources = Sources::default();
+    let mut diagnostics = Vec::new();
+
+    for (key, product) in &config.products {
+        let mut report = |field: &str, message: String| -> Result<()> {
+            let location = Location {
+                config_path,
+                env,
+                key,
+                product,
+            };
+            diagnostics.push(sources.diagnostic(&location, field, message)?);
+            Ok(())
+        };
+
+        let name_length = product.name.chars().count();
+        if product.name.trim().is_empty() {
+            report("name", format!("{}: name must not be empty", key))?;
+        } else if name_length > MAX_NAME_LENGTH {
+            report(
+                "name",
+                format!(
+                    "{}: name is {} characters, Roblox allows at most {}",
+                    key, name_length, MAX_NAME_LENGTH
+                ),
+            )?;
+        }
+
+        if !(MIN_PRICE..=MAX_PRICE).contains(&product.price) {
+            report(
+                "price",
+                format!(
+                    "{}: price {} is out of range, Roblox allows {} to {}",
+                    key, product.price, MIN_PRICE, MAX_PRICE
+                ),
+            )?;
+        }
+
+        if let Some(ref description) = product.description {
+            let length = description.chars().count();
+            if length > MAX_DESCRIPTION_LENGTH {
+                report(
+                    "description",
+                    format!(
+                        "{}: description is {} characters, Roblox allows at most {}",
+                        key, length, MAX_DESCRIPTION_LENGTH
+                    ),
+                )?;
+            }
+        }
+
+        if let Some(ref image) = product.image {
+            if let Some(problem) = check_image(image) {
+                report("image", format!("{}: {}", key, problem))?;
+            }
+        }
+
+        if product.product_type == ProductType::Gamepass && product.store_page.is_some() {
+            report(
+                "store_page",
+                format!("{}: store_page only applies to dev products", key),
+            )?;
+        }
+    }
+
+    diagnostics.sort_by(|a, b| (&a.file, a.line, a.column).cmp(&(&b.file, b.line, b.column)));
+    Ok(diagnostics)
+}
+
+/// Describe what is wrong with an icon file, if anything.
+fn check_image(path: &str) -> Option<String> {
+    let extension = Path::new(path)
+        .extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_lowercase());
+
+    let Some(extension) = extension.filter(|e| IMAGE_FORMATS.contains(&e.as_str())) else {
+        return Some(format!(
+            "image {} is not a supported format ({})",
+            path,
+            IMAGE_FORMATS.join(", ")
+        ));
+    };
+
+    let bytes = match fs::read(path) {
+        Ok(bytes) => bytes,
+        Err(_) => return Some(format!("image {} not found", path)),
+    };
+
+    let matches = match extension.as_str() {
+        "png" => bytes.starts_with(b"\x89PNG\r\n\x1a\n"),
+        "jpg" | "jpeg" => bytes.starts_with(&[0xFF, 0xD8, 0xFF]),
+        "bmp" => bytes.starts_with(b"BM"),
+        _ => true,
+    };
+
+    (!matches).then(|| format!("image {} is not a valid {} file", path, extension))
+}
+
+/// Where a product's fields may be defined.
+struct Location<'a> {
+    config_path: &'a str,
+    env: Option<&'a str>,
+    key: &'a str,
+    product: &'a Product,
+}
+
+/// Parsed config files, kept so keys can be mapped back to line and column.
+#[derive(Default)]
+struct Sources {
+    documents: HashMap<String, ImDocument<String>>,
+}
+
+impl Sources {
+    fn document(&mut self, path: &str) -> Result<&ImDocument<String>> {
+        if !self.documents.contains_key(path) {
+            let content = fs::read_to_string(path)
+                .with_context(|| format!("Failed to read config file: {}", path))?;
+            let document = ImDocument::parse(content)
+                .with_context(|| format!("Failed to parse config file: {}", path))?;
+            self.documents.insert(path.to_string(), document);
+        }
+        Ok(&self.documents[path])
+    }
+
+    /// Build a diagnostic for `field`, pointing at the environment override,
+    /// product or template that set it. Falls back to the product's header.
+    fn diagnostic(
+        &mut self,
+        location: &Location,
+        field: &str,
+        message: String,
+    ) -> Result<Diagnostic> {
+        let product_file = location
+            .product
+            .source
+            .as_deref()
+            .unwrap_or(location.config_path);
+
+        let mut candidates: Vec<(&str, Vec<&str>)> = Vec::new();
+        if let Some(env) = location.env {
+            candidates.push((
+                location.config_path,
+                vec!["environments", env, "overrides", location.key, field],
+            ));
+        }
+        candidates.push((product_file, vec!["products", location.key, field]));
+
+        // Follow the template chain, guarding against cycles in the raw file
+        let mut template = location.product.extends.clone();
+        let mut seen = Vec::new();
+        while let Some(name) = template.filter(|name| !seen.contains(name)) {
+            let document = self.document(location.config_path)?;
+            template = lookup(document, &["templates", &name, "extends"])
+                .and_then(|(_, item)| item.as_str().map(str::to_string));
+            seen.push(name);
+        }
+        for name in &seen {
+            candidates.push((
+                location.config_path,
+                vec!["templates", name.as_str(), field],
+            ));
+        }
+        candidates.push((product_file, vec!["products", location.key]));
+
+        for (file, path) in candidates {
+            let document = self.document(file)?;
+            if let Some((span, _)) = lookup(document, &path) {
+                let (line, column) = line_column(document.raw(), span.start);
+                return Ok(Diagnostic {
+                    file: file.to_string(),
+                    line,
+                    column,
+                    message,
+                });
+            }
+        }
+
+        Ok(Diagnostic {
+            file: product_file.to_string(),
+            line: 1,
+            column: 1,
+            message,
+        })
+    }
+}
+
+/// Find the key at `path` in a document, returning its span and value.
+fn lookup<'a>(
+    document: &'a ImDocument<String>,
+    path: &[&str],
+) -> Option<(Range<usize>, &'a toml_edit::Item)> {
+    let (last, parents) = path.split_last()?;
+    let mut table = document.as_table() as &dyn toml_edit::TableLike;
+
+    for key in parents {
+        table = table.get(key)?.as_table_like()?;
+    }
+
+    let (key, item) = table.get_key_value(last)?;
+    Some((key.span()?, item))
+}
+
+/// 1-based line and column of a byte offset
+fn line_column(content: &str, offset: usize) -> (usize, usize) {
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before
+        .rsplit('\n')
+        .next()
+        .map(|l| l.chars().count())
+        .unwrap_or(0)
+        + 1;
+    (line, column)
+}
@@ -0,0 +1,493 @@
+//! An in-process mock of the Open Cloud developer product and game pass APIs,
+//! plus helpers for running the spearmint binary against it.
+
+#![allow(dead_code)]
+
+use axum::extract::{Multipart, Path, Query, Request, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::middleware::{self, Next};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{get, patch};
+use axum::{Json, Router};
+use serde_json::{json, Value};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::path::PathBuf;
+use std::process::Output;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tempfile::TempDir;
+
+pub const API_KEY: &str = "test-api-key";
+
+/// Largest page the mock returns from list endpoints, so pagination is exercised
+const MAX_PAGE_SIZE: usize = 2;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RemoteItem {
+    pub name: String,
+    pub price: u64,
+    pub description: String,
+    pub is_for_sale: bool,
+    pub has_icon: bool,
+    pub regional_pricing: bool,
+    pub store_page: bool,
+}
+
+impl RemoteItem {
+    pub fn new(name: &str, price: u64) -> Self {
+        Self {
+            name: name.to_string(),
+            price,
+            description: String::new(),
+            is_for_sale: true,
+            has_icon: false,
+            regional_pricing: false,
+            store_page: false,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct MockState {
+    pub dev_products: BTreeMap<u64, RemoteItem>,
+    pub gamepasses: BTreeMap<u64, RemoteItem>,
+    /// Every request received, as "METHOD /path"
+    pub requests: Vec<String>,
+    /// Status codes returned, in order, instead of handling the next requests
+    pub failures: VecDeque<u16>,
+    /// Number of upcoming requests that are handled, but answered too late for
+    /// a one second client timeout
+    pub stalls: usize,
+    /// File whose contents are recorded as each request arrives
+    pub watched: Option<PathBuf>,
+    /// Contents of the watched file at each request, alongside `requests`
+    pub snapshots: Vec<Option<String>>,
+    next_id: u64,
+}
+
+impl MockState {
+    fn next_id(&mut self) -> u64 {
+        self.next_id += 1;
+        1000 + self.next_id
+    }
+}
+
+type SharedState = Arc<Mutex<MockState>>;
+
+pub struct MockRoblox {
+    pub url: String,
+    pub state: SharedState,
+}
+
+impl MockRoblox {
+    pub async fn start() -> Self {
+        let state = SharedState::default();
+
+        let app = Router::new()
+            .route(
+                "/developer-products/v2/universes/{universe}/developer-products",
+                axum::routing::post(create_dev_product),
+            )
+            .route(
+                "/developer-products/v2/universes/{universe}/developer-products/creator",
+                get(list_dev_products),
+            )
+            .route(
+                "/developer-products/v2/universes/{universe}/developer-products/{id}",
+                patch(update_dev_product),
+            )
+            .route(
+                "/developer-products/v2/universes/{universe}/developer-products/{id}/creator",
+                get(get_dev_product),
+            )
+            .route(
+                "/game-passes/v1/universes/{universe}/game-passes",
+                axum::routing::post(create_gamepass),
+            )
+            .route(
+                "/game-passes/v1/universes/{universe}/game-passes/creator",
+                get(list_gamepasses),
+            )
+            .route(
+                "/game-passes/v1/universes/{universe}/game-passes/{id}",
+                patch(update_gamepass),
+            )
+            .route(
+                "/game-passes/v1/universes/{universe}/game-passes/{id}/creator",
+                get(get_gamepass),
+            )
+            .layer(middleware::from_fn_with_state(state.clone(), intercept))
+            .with_state(state.clone());
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+
+        Self { url, state }
+    }
+
+    /// Fail the next request with the given status
+    pub fn fail_next(&self, status: u16) {
+        self.state.lock().unwrap().failures.push_back(status);
+    }
+
+    /// Record the contents of a file as each later request arrives
+    pub fn watch(&self, path: PathBuf) {
+        self.state.lock().unwrap().watched = Some(path);
+    }
+
+    /// Each request received since `watch`, with the watched file's contents
+    /// at the time
+    pub fn snapshots(&self) -> Vec<(String, Option<String>)> {
+        let state = self.state.lock().unwrap();
+        let skipped = state.requests.len() - state.snapshots.len();
+        state.requests[skipped..]
+            .iter()
+            .cloned()
+            .zip(state.snapshots.iter().cloned())
+            .collect()
+    }
+
+    /// Handle the next request, but only respond after the client times out
+    pub fn stall_next(&self) {
+        self.state.lock().unwrap().stalls += 1;
+    }
+
+    pub fn dev_products(&self) -> BTreeMap<u64, RemoteItem> {
+        self.state.lock().unwrap().dev_products.clone()
+    }
+
+    pub fn gamepasses(&self) -> BTreeMap<u64, RemoteItem> {
+        self.state.lock().unwrap().gamepasses.clone()
+    }
+
+    pub fn insert_dev_product(&self, item: RemoteItem) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id();
+        state.dev_products.insert(id, item);
+        id
+    }
+
+    pub fn insert_gamepass(&self, item: RemoteItem) -> u64 {
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id();
+        state.gamepasses.insert(id, item);
+        id
+    }
+
+    pub fn edit_gamepass(&self, id: u64, edit: impl FnOnce(&mut RemoteItem)) {
+        edit(self.state.lock().unwrap().gamepasses.get_mut(&id).unwrap());
+    }
+
+    /// Number of requests received with the given method
+    pub fn count(&self, method: &str) -> usize {
+        let prefix = format!("{} ", method);
+        self.state
+            .lock()
+            .unwrap()
+            .requests
+            .iter()
+            .filter(|r| r.starts_with(&prefix))
+            .count()
+    }
+
+    pub fn clear_requests(&self) {
+        self.state.lock().unwrap().requests.clear();
+    }
+}
+
+/// Records every request, checks the API key and injects queued failures
+async fn intercept(State(state): State<SharedState>, request: Request, next: Next) -> Response {
+    let (failure, stall) = {
+        let mut state = state.lock().unwrap();
+        state
+            .requests
+            .push(format!("{} {}", request.method(), request.uri().path()));
+        if let Some(path) = state.watched.clone() {
+            state.snapshots.push(std::fs::read_to_string(path).ok());
+        }
+        let stall = state.failures.is_empty() && state.stalls > 0;
+        if stall {
+            state.stalls -= 1;
+        }
+        (state.failures.pop_front(), stall)
+    };
+
+    if request.headers().get("x-api-key").map(|v| v.as_bytes()) != Some(API_KEY.as_bytes()) {
+        return (StatusCode::UNAUTHORIZED, "missing api key").into_response();
+    }
+
+    if let Some(status) = failure {
+        let mut headers = HeaderMap::new();
+        if status == 429 || status == 503 {
+            headers.insert("retry-after", "0".parse().unwrap());
+        }
+        let status = StatusCode::from_u16(status).unwrap();
+        return (status, headers, "injected failure").into_response();
+    }
+
+    let response = next.run(request).await;
+    if stall {
+        tokio::time::sleep(Duration::from_secs(2)).await;
+    }
+    response
+}
+
+async fn read_form(mut multipart: Multipart) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    while let Some(field) = multipart.next_field().await.unwrap() {
+        let name = field.name().unwrap().to_string();
+        if name == "imageFile" {
+            field.bytes().await.unwrap();
+            fields.insert(name, String::new());
+        } else {
+            fields.insert(name, field.text().await.unwrap());
+        }
+    }
+    fields
+}
+
+fn apply_form(item: &mut RemoteItem, form: &HashMap<String, String>) {
+    if let Some(name) = form.get("name") {
+        item.name = name.clone();
+    }
+    if let Some(price) = form.get("price") {
+        item.price = price.parse().unwrap();
+    }
+    if let Some(description) = form.get("description") {
+        item.description = description.clone();
+    }
+    if let Some(is_for_sale) = form.get("isForSale") {
+        item.is_for_sale = is_for_sale.parse().unwrap();
+    }
+    if let Some(regional_pricing) = form.get("isRegionalPricingEnabled") {
+        item.regional_pricing = regional_pricing.parse().unwrap();
+    }
+    if let Some(store_page) = form.get("storePageEnabled") {
+        item.store_page = store_page.parse().unwrap();
+    }
+    if form.contains_key("imageFile") {
+        item.has_icon = true;
+    }
+}
+
+fn details(id_field: &str, id: u64, item: &RemoteItem) -> Value {
+    let features: Vec<&str> = if item.regional_pricing {
+        vec!["RegionalPricing"]
+    } else {
+        Vec::new()
+    };
+    let mut details = json!({
+        id_field: id,
+        "name": item.name,
+        "description": item.description,
+        "isForSale": item.is_for_sale,
+        "priceInformation": {
+            "defaultPriceInRobux": item.price,
+            "enabledFeatures": features,
+        },
+    });
+    // Only dev products have a store page setting
+    if id_field == "productId" {
+        details["storePageEnabled"] = json!(item.store_page);
+    }
+    // Like Roblox, off-sale gamepasses have no price
+    if id_field == "gamePassId" && !item.is_for_sale {
+        details["priceInformation"] = Value::Null;
+    }
+    details
+}
+
+fn page(
+    items: &BTreeMap<u64, RemoteItem>,
+    id_field: &str,
+    list_field: &str,
+    query: &HashMap<String, String>,
+) -> Value {
+    let size = query
+        .get("pageSize")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(MAX_PAGE_SIZE)
+        .min(MAX_PAGE_SIZE);
+    let start: usize = query
+        .get("pageToken")
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(0);
+
+    let entries: Vec<Value> = items
+        .iter()
+        .skip(start)
+        .take(size)
+        .map(|(id, item)| details(id_field, *id, item))
+        .collect();
+    let next = (start + size < items.len()).then(|| (start + size).to_string());
+
+    json!({ list_field: entries, "nextPageToken": next })
+}
+
+async fn create_dev_product(State(state): State<SharedState>, multipart: Multipart) -> Json<Value> {
+    let form = read_form(multipart).await;
+    let mut item = RemoteItem::new("", 0);
+    apply_form(&mut item, &form);
+
+    let mut state = state.lock().unwrap();
+    let id = state.next_id();
+    state.dev_products.insert(id, item);
+    Json(json!({ "productId": id }))
+}
+
+async fn update_dev_product(
+    State(state): State<SharedState>,
+    Path((_universe, id)): Path<(u64, u64)>,
+    multipart: Multipart,
+) -> StatusCode {
+    let form = read_form(multipart).await;
+    match state.lock().unwrap().dev_products.get_mut(&id) {
+        Some(item) => {
+            apply_form(item, &form);
+            StatusCode::NO_CONTENT
+        }
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+async fn get_dev_product(
+    State(state): State<SharedState>,
+    Path((_universe, id)): Path<(u64, u64)>,
+) -> Response {
+    match state.lock().unwrap().dev_products.get(&id) {
+        Some(item) => Json(details("productId", id, item)).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn list_dev_products(
+    State(state): State<SharedState>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Json<Value> {
+    let state = state.lock().unwrap();
+    Json(page(
+        &state.dev_products,
+        "productId",
+        "developerProducts",
+        &query,
+    ))
+}
+
+async fn create_gamepass(State(state): State<SharedState>, multipart: Multipart) -> Json<Value> {
+    let form = read_form(multipart).await;
+    let mut item = RemoteItem::new("", 0);
+    apply_form(&mut item, &form);
+
+    let mut state = state.lock().unwrap();
+    let id = state.next_id();
+    state.gamepasses.insert(id, item);
+    Json(json!({ "gamePassId": id }))
+}
+
+async fn update_gamepass(
+    State(state): State<SharedState>,
+    Path((_universe, id)): Path<(u64, u64)>,
+    multipart: Multipart,
+) -> StatusCode {
+    let form = read_form(multipart).await;
+    match state.lock().unwrap().gamepasses.get_mut(&id) {
+        Some(item) => {
+            apply_form(item, &form);
+            StatusCode::NO_CONTENT
+        }
+        None => StatusCode::NOT_FOUND,
+    }
+}
+
+async fn get_gamepass(
+    State(state): State<SharedState>,
+    Path((_universe, id)): Path<(u64, u64)>,
+) -> Response {
+    match state.lock().unwrap().gamepasses.get(&id) {
+        Some(item) => Json(details("gamePassId", id, item)).into_response(),
+        None => StatusCode::NOT_FOUND.into_response(),
+    }
+}
+
+async fn list_gamepasses(
+    State(state): State<SharedState>,
+    Query(query): Query<HashMap<String, String>>,
+) -> Json<Value> {
+    let state = state.lock().unwrap();
+    Json(page(&state.gamepasses, "gamePassId", "gamePasses", &query))
+}
+
+/// A temporary project directory wired up to a fresh mock server.
+pub struct Project {
+    pub dir: TempDir,
+    pub mock: MockRoblox,
+}
+
+impl Project {
+    pub async fn new(config: &str) -> Self {
+        let project = Self {
+            dir: TempDir::new().unwrap(),
+            mock: MockRoblox::start().await,
+        };
+        project.write("spearmint.toml", config);
+        project
+    }
+
+    pub fn write(&self, path: &str, content: &str) {
+        let path = self.dir.path().join(path);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).unwrap();
+        }
+        std::fs::write(path, content).unwrap();
+    }
+
+    pub fn read(&self, path: &str) -> String {
+        std::fs::read_to_string(self.dir.path().join(path)).unwrap()
+    }
+
+    pub fn exists(&self, path: &str) -> bool {
+        self.dir.path().join(path).exists()
+    }
+
+    /// The entries of the default lock file
+    pub fn lock(&self) -> toml::Table {
+        let mut lock: toml::Table = toml::from_str(&self.read("spearmint.lock.toml")).unwrap();
+        match lock.remove("products") {
+            Some(toml::Value::Table(products)) => products,
+            _ => toml::Table::new(),
+        }
+    }
+
+    /// Run spearmint in the project directory against the mock server
+    pub async fn run(&self, args: &[&str]) -> Output {
+        self.run_with_env(args, &[]).await
+    }
+
+    /// Run spearmint with extra environment variables
+    pub async fn run_with_env(&self, args: &[&str], vars: &[(&str, String)]) -> Output {
+        let output = tokio::process::Command::new(env!("CARGO_BIN_EXE_spearmint"))
+            .args(args)
+            .current_dir(self.dir.path())
+            .env("ROBLOX_PRODUCTS_API_KEY", API_KEY)
+            .env("SPEARMINT_API_URL", &self.mock.url)
+            .env_remove("GITHUB_STEP_SUMMARY")
+            .envs(vars.iter().map(|(name, value)| (name, value)))
+            .output()
+            .await
+            .unwrap();
+
+        eprintln!(
+            "$ spearmint {}\n{}{}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        output
+    }
+}
+
+pub fn stdout(output: &Output) -> String {
+    String::from_utf8_lossy(&output.stdout).into_owned()
+}
@@ -0,0 +1,1098 @@
+mod common;
+
+use common::{stdout, Project, RemoteItem};
+
+const CONFIG: &str = r#"
+universe_id = 1
+
+[output]
+path = "out/Products.luau"
+typescript = true
+
+[retry]
+base_delay_ms = 1
+
+[products.coins_100]
+type = "dev_product"
+name = "100 Coins"
+price = 99
+description = "Get 100 coins"
+
+[products.vip]
+type = "gamepass"
+name = "VIP"
+price = 499
+"#;
+
+#[tokio::test]
+async fn sync_creates_products_and_writes_lock() {
+    let project = Project::new(CONFIG).await;
+
+    let output = project.run(&["sync"]).await;
+    assert!(output.status.success());
+
+    let dev_products = project.mock.dev_products();
+    let gamepasses = project.mock.gamepasses();
+    assert_eq!(dev_products.len(), 1);
+    assert_eq!(gamepasses.len(), 1);
+
+    let (coins_id, coins) = dev_products.iter().next().unwrap();
+    assert_eq!(coins.name, "100 Coins");
+    assert_eq!(coins.price, 99);
+    assert_eq!(coins.description, "Get 100 coins");
+
+    let (vip_id, vip) = gamepasses.iter().next().unwrap();
+    assert_eq!(vip.name, "VIP");
+    assert!(vip.is_for_sale);
+
+    let lock = project.lock();
+    assert_eq!(
+        lock["coins_100"]["roblox_id"].as_integer(),
+        Some(*coins_id as i64)
+    );
+    assert_eq!(lock["vip"]["roblox_id"].as_integer(), Some(*vip_id as i64));
+
+    let lua = project.read("out/Products.luau");
+    assert!(lua.contains(&format!("[\"coins_100\"] = {}", coins_id)));
+    assert!(lua.starts_with("--!strict\n"));
+    assert!(lua.contains("export type DevProductKey = \"coins_100\"\n"));
+    assert!(lua.contains("\tGamepasses: {\n\t\t[\"vip\"]: number,\n\t},"));
+    assert!(lua.contains("local Products: Products = {"));
+    assert!(lua.contains(&format!(
+        "\tDevProductsById = {{\n\t\t[{}] = \"coins_100\",\n\t}},",
+        coins_id
+    )));
+    assert!(lua.contains(&format!("[{}] = \"vip\"", vip_id)));
+    assert!(lua.contains("\tGamepassesById: { [number]: GamepassKey },"));
+    assert!(project
+        .read("out/Products.d.ts")
+        .contains("readonly [id: number]: keyof Products[\"DevProducts\"];"));
+}
+
+#[tokio::test]
+async fn metadata_output_includes_product_fields() {
+    let config = CONFIG.replace(
+        "typescript = true",
+        "typescript = true\nmode = \"metadata\"",
+    );
+    let project = Project::new(&config.replace("Get 100 coins", "Get \\\"100\\\" coins")).await;
+    assert!(project.run(&["sync"]).await.status.success());
+
+    let coins_id = *project.mock.dev_products().keys().next().unwrap();
+    let lua = project.read("out/Products.luau");
+    assert!(lua.contains(&format!(
+        "[\"coins_100\"] = {{\n\t\t\tid = {},\n\t\t\ttype = \"DevProduct\",\n\t\t\tname = \"100 Coins\",\n\t\t\tprice = 99,\n\t\t\tdescription = \"Get \\\"100\\\" coins\",\n\t\t\toffsale = false,\n\t\t}}",
+        coins_id
+    )));
+
+    assert!(lua.contains("[\"vip\"]: ProductInfo,"));
+
+    let dts = project.read("out/Products.d.ts");
+    assert!(dts.contains("readonly name: \"VIP\";"));
+    assert!(dts.contains("readonly description: undefined;"));
+    assert!(dts.contains("readonly price: 499;"));
+}
+
+#[tokio::test]
+async fn typescript_keys_are_escaped() {
+    let project = Project::new(
+        r#"
+universe_id = 1
+
+[output]
+path = "out/Products.luau"
+typescript = true
+
+[products.'say "hi" \ bye']
+type = "gamepass"
+name = "Hi"
+price = 10
+product_id = 5
+"#,
+    )
+    .await;
+    assert!(project.run(&["generate"]).await.status.success());
+
+    let dts = project.read("out/Products.d.ts");
+    assert!(dts.contains(r#"readonly "say \"hi\" \\ bye": 5;"#));
+    assert!(project
+        .read("out/Products.luau")
+        .contains(r#"["say \"hi\" \\ bye"] = 5,"#));
+}
+
+#[tokio::test]
+async fn output_can_use_custom_templates() {
+    let config = CONFIG.replace(
+        "typescript = true",
+        "typescript = true\ntemplate = \"codegen/products.luau.j2\"\ntypescript_template = \"codegen/products.d.ts.j2\"",
+    );
+    let project = Project::new(&config).await;
+    project.write(
+        "codegen/products.luau.j2",
+        r#"-- Universe {{ universe_id }}
+return table.freeze({
+	Shop = table.freeze({
+{%- for product in dev_products %}
+		{{ product.key }} = { id = {{ product.id }}, name = {{ product.name | lua_string }}, price = {{ product.price }} },
+{%- endfor %}
+	}),
+	Count = {{ products | length }},
+})
+"#,
+    );
+    project.write(
+        "codegen/products.d.ts.j2",
+        "{% for product in gamepasses %}export declare const {{ product.key }}: {{ product.id }};\n{% endfor %}",
+    );
+    assert!(project.run(&["sync"]).await.status.success());
+
+    let coins_id = *project.mock.dev_products().keys().next().unwrap();
+    let vip_id = *project.mock.gamepasses().keys().next().unwrap();
+    assert_eq!(
+        project.read("out/Products.luau"),
+        format!(
+            "-- Universe 1\nreturn table.freeze({{\n\tShop = table.freeze({{\n\t\tcoins_100 = {{ id = {}, name = \"100 Coins\", price = 99 }},\n\t}}),\n\tCount = 2,\n}})\n",
+            coins_id
+        )
+    );
+    assert_eq!(
+        project.read("out/Products.d.ts"),
+        format!("export declare const vip: {};\n", vip_id)
+    );
+
+    project.write("codegen/products.luau.j2", "{{ product_count }}");
+    let output = project.run(&["generate"]).await;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Failed to render template: codegen/products.luau.j2"));
+}
+
+#[tokio::test]
+async fn templates_are_relative_to_the_config() {
+    let project = Project::new("").await;
+    project.write(
+        "game/spearmint.toml",
+        "universe_id = 7\n\n[output]\npath = \"out/Products.luau\"\ntemplate = \"codegen/products.luau.j2\"\n",
+    );
+    project.write(
+        "game/codegen/products.luau.j2",
+        "-- Universe {{ universe_id }}\n",
+    );
+
+    let output = project
+        .run(&["generate", "--config", "game/spearmint.toml"])
+        .await;
+    assert!(output.status.success());
+    assert_eq!(project.read("out/Products.luau"), "-- Universe 7\n");
+}
+
+#[tokio::test]
+async fn each_output_has_its_own_format_and_filter() {
+    let config = CONFIG
+        .replace("\nuniverse_id", "\nversion = 2\nuniverse_id")
+        .replace("[output]", "[output.main]")
+        .replace(
+            "\n[retry]",
+            r#"
+[output.client]
+path = "out/client/Products.luau"
+filter = { types = ["gamepass"] }
+
+[output.manifest]
+path = "out/products.json"
+format = "json"
+mode = "metadata"
+filter = { exclude = ["coins_*"] }
+
+[retry]"#,
+        );
+    let project = Project::new(&config).await;
+    assert!(project.run(&["sync"]).await.status.success());
+
+    let coins_id = *project.mock.dev_products().keys().next().unwrap();
+    let vip_id = *project.mock.gamepasses().keys().next().unwrap();
+    assert!(project
+        .read("out/Products.luau")
+        .contains(&format!("[\"coins_100\"] = {}", coins_id)));
+
+    let client = project.read("out/client/Products.luau");
+    assert!(!client.contains("coins_100"));
+    assert!(client.contains(&format!("[\"vip\"] = {}", vip_id)));
+    assert!(!project.exists("out/client/Products.d.ts"));
+
+    let manifest: serde_json::Value =
+        serde_json::from_str(&project.read("out/products.json")).unwrap();
+    assert_eq!(manifest["DevProducts"], serde_json::json!({}));
+    assert_eq!(manifest["Gamepasses"]["vip"]["id"], vip_id);
+    assert_eq!(manifest["Gamepasses"]["vip"]["price"], 499);
+    assert_eq!(
+        manifest["GamepassesById"][vip_id.to_string()],
+        serde_json::json!("vip")
+    );
+
+    project.write(
+        "spearmint.toml",
+        &format!(
+            "{}\n[environments.prod]\nuniverse_id = 2\noutput = {{ mian = \"out/Products.prod.luau\" }}\n",
+            config
+        ),
+    );
+    let output = project.run(&["generate", "--env", "prod"]).await;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "Environment \"prod\" sets the path of unknown output \"mian\" (available: main, client, manifest)"
+    ));
+}
+
+#[tokio::test]
+async fn sync_skips_unchanged_products() {
+    let project = Project::new(CONFIG).await;
+    assert!(project.run(&["sync"]).await.status.success());
+    project.mock.clear_requests();
+
+    let output = project.run(&["sync"]).await;
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("2 unchanged"));
+    assert_eq!(project.mock.count("POST"), 0);
+    assert_eq!(project.mock.count("PATCH"), 0);
+}
+
+#[tokio::test]
+async fn sync_updates_changed_products() {
+    let project = Project::new(CONFIG).await;
+    assert!(project.run(&["sync"]).await.status.success());
+    project.mock.clear_requests();
+
+    project.write("spearmint.toml", &CONFIG.replace("499", "399"));
+    let output = project.run(&["sync"]).await;
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("1 updated, 1 unchanged"));
+    assert_eq!(project.mock.count("PATCH"), 1);
+
+    let vip = project.mock.gamepasses().into_values().next().unwrap();
+    assert_eq!(vip.price, 399);
+    assert_eq!(project.lock()["vip"]["price"].as_integer(), Some(399));
+}
+
+#[tokio::test]
+async fn force_sync_updates_every_product() {
+    let project = Project::new(CONFIG).await;
+    assert!(project.run(&["sync"]).await.status.success());
+    project.mock.clear_requests();
+
+    let output = project.run(&["sync", "--force"]).await;
+    assert!(output.status.success());
+    assert_eq!(project.mock.count("POST"), 0);
+    assert_eq!(project.mock.count("PATCH"), 2);
+}
+
+#[tokio::test]
+async fn sync_retries_rate_limited_and_server_errors() {
+    let project = Project::new(CONFIG).await;
+    project.mock.fail_next(429);
+    project.mock.fail_next(503);
+    project.mock.fail_next(429);
+
+    let output = project.run(&["sync", "--jobs", "1"]).await;
+    assert!(output.status.success());
+    assert_eq!(project.mock.dev_products().len(), 1);
+    assert_eq!(project.mock.gamepasses().len(), 1);
+}
+
+#[tokio::test]
+async fn concurrent_syncs_share_rate_limit_backoff() {
+    let mut config = String::from("universe_id = 1\n\n[retry]\nbase_delay_ms = 1\n");
+    for i in 0..8 {
+        config.push_str(&format!(
+            "\n[products.pack_{}]\ntype = \"dev_product\"\nname = \"Pack {}\"\nprice = 10\n",
+            i, i
+        ));
+    }
+    let project = Project::new(&config).await;
+    project.mock.fail_next(429);
+    project.mock.fail_next(429);
+    project.mock.fail_next(429);
+
+    let output = project.run(&["sync", "--jobs", "4"]).await;
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Rate limited"));
+
+    // Every product finished, and none was created twice
+    let mut names: Vec<String> = project
+        .mock
+        .dev_products()
+        .into_values()
+        .map(|item| item.name)
+        .collect();
+    names.sort();
+    let expected: Vec<String> = (0..8).map(|i| format!("Pack {}", i)).collect();
+    assert_eq!(names, expected);
+    assert_eq!(project.lock().len(), 8);
+    assert_eq!(project.mock.count("POST"), 8 + 3);
+}
+
+#[tokio::test]
+async fn creates_are_not_retried_after_server_errors() {
+    let project = Project::new(CONFIG).await;
+    project.mock.fail_next(500);
+
+    let output = project.run(&["sync", "--jobs", "1"]).await;
+    assert_eq!(output.status.code(), Some(1));
+
+    // Roblox may have created the product before failing, so it isn't sent again
+    assert_eq!(project.mock.count("POST"), 2);
+    assert_eq!(project.lock().len(), 1);
+}
+
+#[tokio::test]
+async fn creates_are_not_retried_after_timeouts() {
+    let project =
+        Project::new(&CONFIG.replace("base_delay_ms = 1", "base_delay_ms = 1\ntimeout_secs = 1"))
+            .await;
+    project.mock.stall_next();
+
+    let output = project.run(&["sync", "--jobs", "1"]).await;
+    assert_eq!(output.status.code(), Some(1));
+
+    // The first create reached Roblox but timed out, so it isn't sent again
+    assert_eq!(project.mock.count("POST"), 2);
+    assert_eq!(
+        project.mock.dev_products().len() + project.mock.gamepasses().len(),
+        2
+    );
+    assert_eq!(project.lock().len(), 1);
+}
+
+#[tokio::test]
+async fn lock_is_saved_after_each_product() {
+    let project = Project::new(CONFIG).await;
+    project
+        .mock
+        .watch(project.dir.path().join("spearmint.lock.toml"));
+
+    let output = project.run(&["sync", "--jobs", "1"]).await;
+    assert!(output.status.success());
+
+    // The first product was already locked when the second was created
+    let creates: Vec<Option<String>> = project
+        .mock
+        .snapshots()
+        .into_iter()
+        .filter(|(request, _)| request.starts_with("POST "))
+        .map(|(_, lock)| lock)
+        .collect();
+    assert_eq!(creates.len(), 2);
+    assert_eq!(creates[0], None);
+    assert!(creates[1]
+        .as_deref()
+        .is_some_and(|lock| lock.contains("[products.coins_100]") && !lock.contains("vip")));
+
+    assert_eq!(project.lock().len(), 2);
+    assert!(!project.exists("spearmint.lock.toml.tmp"));
+}
+
+#[tokio::test]
+async fn sync_stops_starting_products_when_the_lock_cannot_be_saved() {
+    let project = Project::new(CONFIG).await;
+    // A directory in the way of the temporary file makes every save fail
+    std::fs::create_dir(project.dir.path().join("spearmint.lock.toml.tmp")).unwrap();
+
+    let output = project.run(&["sync", "--jobs", "1"]).await;
+    assert_eq!(output.status.code(), Some(1));
+    let coins_id = *project.mock.dev_products().keys().next().unwrap();
+    assert!(String::from_utf8_lossy(&output.stderr).contains(&format!(
+        "These products exist on Roblox but are missing from it: coins_100 ({})",
+        coins_id
+    )));
+
+    // Nothing new is created once products can't be recorded
+    assert_eq!(project.mock.count("POST"), 1);
+}
+
+#[tokio::test]
+async fn job_summary_is_opt_in_and_never_fails_the_sync() {
+    let project = Project::new(CONFIG).await;
+    let summary = project.dir.path().join("summary.md");
+    let vars = [(
+        "GITHUB_STEP_SUMMARY",
+        summary.to_string_lossy().into_owned(),
+    )];
+
+    let output = project.run_with_env(&["sync"], &vars).await;
+    assert!(output.status.success());
+    assert!(!project.exists("summary.md"));
+
+    let output = project.run_with_env(&["sync", "--summary"], &vars).await;
+    assert!(output.status.success());
+    assert!(project
+        .read("summary.md")
+        .contains("### spearmint sync (universe 1)"));
+
+    // An unwritable summary is only a warning
+    let vars = [(
+        "GITHUB_STEP_SUMMARY",
+        project.dir.path().to_string_lossy().into_owned(),
+    )];
+    let output = project.run_with_env(&["sync", "--summary"], &vars).await;
+    assert!(output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("Warning: failed to write job summary")
+    );
+}
+
+#[tokio::test]
+async fn sync_reports_failures_and_keeps_successes() {
+    let project = Project::new(CONFIG).await;
+    project.mock.fail_next(400);
+
+    let output = project
+        .run(&["sync", "--jobs", "1", "--report", "report.json"])
+        .await;
+    assert_eq!(output.status.code(), Some(1));
+
+    // One product failed without retrying, the other was still created and locked
+    let lock = project.lock();
+    assert_eq!(lock.len(), 1);
+    assert_eq!(
+        project.mock.dev_products().len() + project.mock.gamepasses().len(),
+        1
+    );
+
+    let report: serde_json::Value = serde_json::from_str(&project.read("report.json")).unwrap();
+    let failed: Vec<_> = report["products"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .filter(|p| p["outcome"] == "failed")
+        .collect();
+    assert_eq!(failed.len(), 1);
+    assert_eq!(failed[0]["error"]["status"], 400);
+    assert_eq!(failed[0]["error"]["kind"], "api");
+}
+
+#[tokio::test]
+async fn plan_exits_with_pending_changes() {
+    let project = Project::new(CONFIG).await;
+
+    let output = project.run(&["plan"]).await;
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stdout(&output).contains("2 to create"));
+
+    assert!(project.run(&["sync"]).await.status.success());
+    project.mock.clear_requests();
+
+    let output = project.run(&["plan"]).await;
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(project.mock.state.lock().unwrap().requests.len(), 0);
+}
+
+#[tokio::test]
+async fn regional_pricing_and_store_page_are_synced() {
+    let config = CONFIG
+        .replace(
+            "description = \"Get 100 coins\"",
+            "description = \"Get 100 coins\"\nregional_pricing = true\nstore_page = true",
+        )
+        .replace("price = 499", "price = 499\nregional_pricing = true");
+    let project = Project::new(&config).await;
+    assert!(project.run(&["sync"]).await.status.success());
+
+    let coins = project.mock.dev_products().into_values().next().unwrap();
+    assert!(coins.regional_pricing);
+    assert!(coins.store_page);
+    let vip = project.mock.gamepasses().into_values().next().unwrap();
+    assert!(vip.regional_pricing);
+    assert_eq!(
+        project.lock()["vip"]["regional_pricing"].as_bool(),
+        Some(true)
+    );
+
+    project.write(
+        "spearmint.toml",
+        &config.replace("store_page = true", "store_page = false"),
+    );
+    let output = project.run(&["plan"]).await;
+    assert!(stdout(&output).contains("store_page: true -> false"));
+    assert!(project.run(&["sync"]).await.status.success());
+    assert!(
+        !project
+            .mock
+            .dev_products()
+            .into_values()
+            .next()
+            .unwrap()
+            .store_page
+    );
+
+    // Unset flags are left alone
+    project.mock.clear_requests();
+    project.write("spearmint.toml", CONFIG);
+    let output = project.run(&["sync"]).await;
+    assert!(stdout(&output).contains("2 unchanged"));
+    assert_eq!(project.mock.count("PATCH"), 0);
+}
+
+#[tokio::test]
+async fn drift_detects_and_refresh_fixes_dashboard_edits() {
+    let project = Project::new(CONFIG).await;
+    assert!(project.run(&["sync"]).await.status.success());
+
+    let vip_id = *project.mock.gamepasses().keys().next().unwrap();
+    project.mock.edit_gamepass(vip_id, |vip| vip.price = 1);
+
+    let output = project.run(&["drift"]).await;
+    assert_eq!(output.status.code(), Some(2));
+    assert!(stdout(&output).contains("price: config 499, lock 499, remote 1"));
+
+    // A plain sync trusts the lock and leaves the drift in place
+    assert!(project.run(&["sync"]).await.status.success());
+    assert_eq!(project.mock.gamepasses()[&vip_id].price, 1);
+
+    assert!(project.run(&["sync", "--refresh"]).await.status.success());
+    assert_eq!(project.mock.gamepasses()[&vip_id].price, 499);
+    assert_eq!(project.run(&["drift"]).await.status.code(), Some(0));
+}
+
+#[tokio::test]
+async fn off_sale_gamepasses_without_a_price_are_not_drift() {
+    let project = Project::new(&CONFIG.replace("price = 499", "price = 499\noffsale = true")).await;
+    assert!(project.run(&["sync"]).await.status.success());
+
+    let output = project.run(&["drift"]).await;
+    assert_eq!(output.status.code(), Some(0));
+
+    project.mock.clear_requests();
+    assert!(project.run(&["sync", "--refresh"]).await.status.success());
+    assert_eq!(project.mock.count("PATCH"), 0);
+    assert_eq!(project.lock()["vip"]["price"].as_integer(), Some(499));
+}
+
+#[tokio::test]
+async fn import_adopts_existing_products() {
+    let project = Project::new("universe_id = 1\n\n[products]\n").await;
+    project
+        .mock
+        .insert_dev_product(RemoteItem::new("100 Coins", 99));
+    project
+        .mock
+        .insert_dev_product(RemoteItem::new("500 Coins", 449));
+    project
+        .mock
+        .insert_dev_product(RemoteItem::new("1000 Coins", 849));
+    let mut vip = RemoteItem::new("VIP", 499);
+    vip.is_for_sale = false;
+    project.mock.insert_gamepass(vip);
+
+    let output = project.run(&["import"]).await;
+    assert!(output.status.success());
+
+    let config: toml::Table = toml::from_str(&project.read("spearmint.toml")).unwrap();
+    let products = config["products"].as_table().unwrap();
+    assert_eq!(products.len(), 4);
+    assert_eq!(products["vip"]["offsale"].as_bool(), Some(true));
+    assert_eq!(products["1000_coins"]["price"].as_integer(), Some(849));
+
+    // The imported config and lock already match Roblox
+    project.mock.clear_requests();
+    let output = project.run(&["sync"]).await;
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("4 unchanged"));
+    assert_eq!(project.mock.count("PATCH"), 0);
+}
+
+#[tokio::test]
+async fn import_keeps_unpriced_gamepasses_off_sale() {
+    let project = Project::new("universe_id = 1\n\n[products]\n").await;
+    let mut vip = RemoteItem::new("VIP", 499);
+    vip.is_for_sale = false;
+    let vip_id = project.mock.insert_gamepass(vip);
+
+    let output = project.run(&["import"]).await;
+    assert!(output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Gamepass \"VIP\" has no price, importing it off sale with price 1"));
+
+    let config: toml::Table = toml::from_str(&project.read("spearmint.toml")).unwrap();
+    assert_eq!(config["products"]["vip"]["price"].as_integer(), Some(1));
+    assert_eq!(config["products"]["vip"]["offsale"].as_bool(), Some(true));
+
+    // The placeholder price passes validation, and nothing is sent
+    project.mock.clear_requests();
+    let output = project.run(&["sync"]).await;
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("1 unchanged"));
+    assert_eq!(project.mock.count("PATCH"), 0);
+    assert!(!project.mock.gamepasses()[&vip_id].is_for_sale);
+}
+
+#[tokio::test]
+async fn orphaned_products_are_archived() {
+    let project = Project::new(CONFIG).await;
+    assert!(project.run(&["sync"]).await.status.success());
+
+    let without_vip = CONFIG.split("[products.vip]").next().unwrap();
+    project.write("spearmint.toml", without_vip);
+
+    let output = project.run(&["sync", "--orphans", "archive"]).await;
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("1 archived"));
+
+    let vip = project.mock.gamepasses().into_values().next().unwrap();
+    assert_eq!(vip.name, "[Archived] VIP");
+    assert!(!vip.is_for_sale);
+
+    // Archiving is only done once
+    project.mock.clear_requests();
+    assert!(project
+        .run(&["sync", "--orphans", "archive"])
+        .await
+        .status
+        .success());
+    assert_eq!(project.mock.count("PATCH"), 0);
+}
+
+#[tokio::test]
+async fn orphaned_dev_products_keep_their_type() {
+    let project = Project::new(CONFIG).await;
+    assert!(project.run(&["sync"]).await.status.success());
+
+    // A dev product entry from before product types were recorded
+    let lock =
+        project
+            .read("spearmint.lock.toml")
+            .replacen("product_type = \"dev_product\"\n", "", 1);
+    project.write("spearmint.lock.toml", &lock);
+    let coins =
+        CONFIG.find("[products.coins_100]").unwrap()..CONFIG.find("[products.vip]").unwrap();
+    let mut without_coins = CONFIG.to_string();
+    without_coins.replace_range(coins, "");
+    project.write("spearmint.toml", &without_coins);
+
+    assert!(project
+        .run(&["sync", "--orphans", "offsale"])
+        .await
+        .status
+        .success());
+    assert_eq!(
+        project.lock()["coins_100"]["product_type"].as_str(),
+        Some("dev_product")
+    );
+
+    // Being off sale doesn't make it look like a gamepass later
+    let output = project.run(&["sync", "--orphans", "archive"]).await;
+    assert!(output.status.success());
+    let coins = project.mock.dev_products().into_values().next().unwrap();
+    assert_eq!(coins.name, "[Archived] 100 Coins");
+}
+
+#[tokio::test]
+async fn products_still_in_use_are_not_orphaned() {
+    let project = Project::new(CONFIG).await;
+    assert!(project.run(&["sync"]).await.status.success());
+    let vip_id = *project.mock.gamepasses().keys().next().unwrap();
+
+    // The key changed without previous_keys, but product_id still points at it
+    project.write(
+        "spearmint.toml",
+        &CONFIG.replace(
+            "[products.vip]",
+            &format!("[products.vip_pass]\nproduct_id = {}", vip_id),
+        ),
+    );
+
+    let output = project.run(&["sync", "--orphans", "archive"]).await;
+    assert!(output.status.success());
+    assert!(!stdout(&output).contains("archived"));
+
+    let vip = project.mock.gamepasses().into_values().next().unwrap();
+    assert_eq!(vip.name, "VIP");
+    assert!(vip.is_for_sale);
+}
+
+#[tokio::test]
+async fn rename_refuses_to_replace_an_orphaned_lock_entry() {
+    let project = Project::new(CONFIG).await;
+    assert!(project.run(&["sync"]).await.status.success());
+    let vip_id = *project.mock.gamepasses().keys().next().unwrap();
+
+    let without_vip = CONFIG.split("[products.vip]").next().unwrap();
+    project.write("spearmint.toml", without_vip);
+
+    let output = project.run(&["rename", "coins_100", "vip"]).await;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(&format!(
+        "Product \"vip\" (ID: {}) already exists in spearmint.lock.toml",
+        vip_id
+    )));
+    assert_eq!(project.read("spearmint.toml"), without_vip);
+    assert_eq!(
+        project.lock()["vip"]["roblox_id"].as_integer(),
+        Some(vip_id as i64)
+    );
+}
+
+#[tokio::test]
+async fn renamed_products_keep_their_roblox_id() {
+    let project = Project::new(CONFIG).await;
+    assert!(project.run(&["sync"]).await.status.success());
+
+    let output = project.run(&["rename", "vip", "vip_pass"]).await;
+    assert!(output.status.success());
+
+    project.mock.clear_requests();
+    let output = project.run(&["sync"]).await;
+    assert!(output.status.success());
+    assert_eq!(project.mock.count("POST"), 0);
+    assert_eq!(project.mock.gamepasses().len(), 1);
+    assert!(project.lock().contains_key("vip_pass"));
+}
+
+#[tokio::test]
+async fn rename_keeps_comments_and_formatting() {
+    let config = CONFIG
+        .replace("[products.vip]", "# Sold in the shop\n[products.vip]")
+        .replace("price = 499", "price = 499 # launch price");
+    let project = Project::new(&config).await;
+
+    let output = project.run(&["rename", "vip", "vip_pass"]).await;
+    assert!(output.status.success());
+
+    let expected = config
+        .replace("[products.vip]", "[products.vip_pass]")
+        .replace(
+            "# launch price",
+            "# launch price\nprevious_keys = [\"vip\"]",
+        );
+    assert_eq!(project.read("spearmint.toml"), expected);
+}
+
+#[tokio::test]
+async fn environments_use_their_own_universe_and_lock() {
+    let config = format!(
+        "{}\n[environments.prod]\nuniverse_id = 2\noutput = \"out/Products.prod.luau\"\n\n[environments.prod.overrides.vip]\nprice = 599\n",
+        CONFIG
+    );
+    let project = Project::new(&config).await;
+
+    let output = project.run(&["sync", "--env", "prod"]).await;
+    assert!(output.status.success());
+    assert!(stdout(&output).contains("universe 2"));
+    assert!(project
+        .mock
+        .state
+        .lock()
+        .unwrap()
+        .requests
+        .iter()
+        .all(|r| r.contains("/universes/2/")));
+
+    let vip = project.mock.gamepasses().into_values().next().unwrap();
+    assert_eq!(vip.price, 599);
+    assert!(project.exists("spearmint.prod.lock.toml"));
+    assert!(!project.exists("spearmint.lock.toml"));
+    assert!(project.exists("out/Products.prod.luau"));
+
+    // The default environment has its own lock, so everything is created again
+    let output = project.run(&["plan"]).await;
+    assert!(stdout(&output).contains("2 to create"));
+
+    let output = project.run(&["list", "--env", "staging"]).await;
+    assert!(!output.status.success());
+}
+
+#[tokio::test]
+async fn included_files_add_products() {
+    let project = Project::new("universe_id = 1\ninclude = [\"products/*.toml\"]\n").await;
+    let (main, vip) = CONFIG.split_once("[products.vip]").unwrap();
+    let coins = main.split_once("[products.coins_100]").unwrap().1;
+    project.write(
+        "products/coins.toml",
+        &format!("[products.coins_100]{}", coins),
+    );
+    project.write("products/passes.toml", &format!("[products.vip]{}", vip));
+
+    assert!(project.run(&["sync"]).await.status.success());
+    assert_eq!(project.lock().len(), 2);
+
+    // Renaming keeps the product in its own file
+    assert!(project
+        .run(&["rename", "vip", "vip_pass"])
+        .await
+        .status
+        .success());
+    assert!(project
+        .read("products/passes.toml")
+        .contains("[products.vip_pass]"));
+    assert!(!project.read("spearmint.toml").contains("vip"));
+
+    project.write(
+        "products/more.toml",
+        &format!("[products.coins_100]{}", coins),
+    );
+    let output = project.run(&["plan"]).await;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains(
+        "Duplicate product key \"coins_100\" in products/coins.toml and products/more.toml"
+    ));
+}
+
+#[tokio::test]
+async fn products_inherit_template_fields() {
+    let project = Project::new(
+        r#"
+universe_id = 1
+
+[templates.pack]
+type = "dev_product"
+description = "Adds {name} for {price} Robux"
+
+[templates.limited_pack]
+extends = "pack"
+offsale = true
+
+[products.coins_100]
+extends = "limited_pack"
+name = "100 Coins"
+price = 99
+
+[products.gems_10]
+extends = "pack"
+name = "10 Gems"
+price = 49
+description = "Shiny"
+"#,
+    )
+    .await;
+
+    assert!(project.run(&["sync"]).await.status.success());
+    let descriptions: Vec<String> = project
+        .mock
+        .dev_products()
+        .into_values()
+        .map(|p| p.description)
+        .collect();
+    assert!(descriptions.contains(&"Adds 100 Coins for 99 Robux".to_string()));
+    assert!(descriptions.contains(&"Shiny".to_string()));
+
+    // Saving the config keeps inherited fields in the template
+    assert!(project
+        .run(&["rename", "coins_100", "coins"])
+        .await
+        .status
+        .success());
+    let config: toml::Table = toml::from_str(&project.read("spearmint.toml")).unwrap();
+    let coins = config["products"]["coins"].as_table().unwrap();
+    assert_eq!(coins["extends"].as_str(), Some("limited_pack"));
+    assert!(!coins.contains_key("type"));
+    assert!(!coins.contains_key("description"));
+
+    project.write(
+        "spearmint.toml",
+        "universe_id = 1\n[products.a]\nextends = \"missing\"\nname = \"A\"\nprice = 1\n",
+    );
+    let output = project.run(&["list"]).await;
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Unknown template \"missing\""));
+}
+
+#[tokio::test]
+async fn prices_are_computed_from_tiers() {
+    let project = Project::new(
+        r#"
+universe_id = 1
+
+[price_tiers]
+tier1 = 49
+tier3 = 249
+
+[templates.pack]
+type = "dev_product"
+price = "tier1"
+description = "Only {price} Robux"
+
+[products.small]
+extends = "pack"
+name = "Small"
+
+[products.large]
+type = "dev_product"
+name = "Large"
+price = "tier3 * 2 + 1"
+
+[environments.prod]
+universe_id = 2
+
+[environments.prod.overrides.small]
+price = "tier3"
+"#,
+    )
+    .await;
+
+    let output = project.run(&["plan"]).await;
+    assert!(stdout(&output).contains("price: (none) -> 499 (tier3 * 2 + 1)"));
+
+    let list = stdout(&project.run(&["list"]).await);
+    assert!(list.contains("Price: 49 Robux (tier1)"));
+
+    assert!(project.run(&["sync"]).await.status.success());
+    let products: Vec<(String, u64, String)> = project
+        .mock
+        .dev_products()
+        .into_values()
+        .map(|p| (p.name, p.price, p.description))
+        .collect();
+    assert!(products.contains(&("Small".to_string(), 49, "Only 49 Robux".to_string())));
+    assert!(products.contains(&("Large".to_string(), 499, String::new())));
+
+    let list = stdout(&project.run(&["list", "--env", "prod"]).await);
+    assert!(list.contains("Price: 249 Robux (tier3)"));
+
+    project.write(
+        "spearmint.toml",
+        "universe_id = 1\n[price_tiers]\ntier1 = 49\n[products.a]\ntype = \"gamepass\"\nname = \"A\"\nprice = \"tier2\"\n",
+    );
+    let output = project.run(&["list"]).await;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("Unknown price tier \"tier2\" (available: tier1)"));
+}
+
+#[tokio::test]
+async fn invalid_products_are_reported_before_syncing() {
+    let config = CONFIG
+        .replace("price = 499", "price = 0\nstore_page = true")
+        .replace(
+            "name = \"100 Coins\"",
+            &format!("name = \"{}\"", "x".repeat(51)),
+        );
+    let project = Project::new(&config).await;
+
+    let output = project.run(&["validate"]).await;
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("spearmint.toml:13:1: coins_100: name is 51 characters"));
+    assert!(stderr.contains("spearmint.toml:20:1: vip: price 0 is out of range"));
+    assert!(stderr.contains("spearmint.toml:21:1: vip: store_page only applies to dev products"));
+
+    for args in [&["plan"][..], &["sync", "--dry-run"]] {
+        let output = project.run(args).await;
+        assert_eq!(output.status.code(), Some(1));
+        assert!(String::from_utf8_lossy(&output.stderr).contains("vip: price 0 is out of range"));
+    }
+
+    let output = project.run(&["sync"]).await;
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(project.mock.state.lock().unwrap().requests.len(), 0);
+}
+
+#[tokio::test]
+async fn init_points_the_config_at_the_schema() {
+    let project = Project::new("").await;
+    std::fs::remove_file(project.dir.path().join("spearmint.toml")).unwrap();
+
+    assert!(project.run(&["init"]).await.status.success());
+    assert!(project
+        .read("spearmint.toml")
+        .starts_with("#:schema ./spearmint.schema.json\n"));
+
+    let schema: serde_json::Value =
+        serde_json::from_str(&project.read("spearmint.schema.json")).unwrap();
+    assert!(schema["properties"]["products"].is_object());
+    assert_eq!(
+        schema["definitions"]["ProductType"]["enum"],
+        serde_json::json!(["dev_product", "gamepass"])
+    );
+
+    // Saving the config keeps the directive
+    let output = project.run(&["rename", "example_product", "example"]).await;
+    assert!(output.status.success());
+    assert!(project.read("spearmint.toml").starts_with("#:schema"));
+}
+
+#[tokio::test]
+async fn migrate_upgrades_old_files_in_place() {
+    // A version 1 config, with a single unnamed output
+    let config = format!(
+        "#:schema ./spearmint.schema.json\n# Shop products\n{}\n[environments.prod]\nuniverse_id = 2\noutput = \"out/Products.prod.luau\"\n",
+        CONFIG
+    );
+    let project = Project::new(&config).await;
+    project.write(
+        "spearmint.lock.toml",
+        "[coins_100]\nroblox_id = 1001\nname = \"100 Coins\"\nprice = 99\n\n[vip]\nroblox_id = 1002\nname = \"VIP\"\nprice = 499\noffsale = false\n",
+    );
+
+    // Old files are still read before migrating
+    let list = stdout(&project.run(&["list"]).await);
+    assert!(list.contains("ID: 1001 (from mapping)"));
+
+    let output = project.run(&["migrate"]).await;
+    assert!(output.status.success());
+    assert_eq!(
+        project.read("spearmint.toml"),
+        config
+            .replace("\nuniverse_id = 1", "\nversion = 2\nuniverse_id = 1")
+            .replace("[output]", "[output.main]")
+            .replace(
+                "output = \"out/Products.prod.luau\"",
+                "output = { main = \"out/Products.prod.luau\" }"
+            )
+    );
+    let lock: toml::Table = toml::from_str(&project.read("spearmint.lock.toml")).unwrap();
+    assert_eq!(lock["version"].as_integer(), Some(2));
+    assert_eq!(
+        project.lock()["coins_100"]["roblox_id"].as_integer(),
+        Some(1001)
+    );
+    // Entries from before product types were recorded get one
+    assert_eq!(
+        project.lock()["coins_100"]["product_type"].as_str(),
+        Some("dev_product")
+    );
+    assert_eq!(
+        project.lock()["vip"]["product_type"].as_str(),
+        Some("gamepass")
+    );
+
+    let output = project.run(&["migrate"]).await;
+    assert!(stdout(&output).contains("spearmint.lock.toml is already up to date"));
+
+    project.write("spearmint.toml", &format!("version = 99\n{}", CONFIG));
+    let output = project.run(&["list"]).await;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr)
+        .contains("spearmint.toml uses config format version 99"));
+}
+
+#[tokio::test]
+async fn products_follow_config_order() {
+    let mut config = String::from("universe_id = 1\n\n[output]\npath = \"Products.luau\"\n");
+    for key in ["zebra", "apple", "mango"] {
+        config.push_str(&format!(
+            "\n[products.{}]\ntype = \"dev_product\"\nname = \"{}\"\nprice = 10\n",
+            key, key
+        ));
+    }
+    let project = Project::new(&config).await;
+
+    let output = project.run(&["sync", "--report", "report.json"]).await;
+    assert!(output.status.success());
+
+    let report: serde_json::Value = serde_json::from_str(&project.read("report.json")).unwrap();
+    let keys: Vec<&str> = report["products"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|p| p["key"].as_str().unwrap())
+        .collect();
+    assert_eq!(keys, ["zebra", "apple", "mango"]);
+
+    let lua = project.read("Products.luau");
+    let positions: Vec<usize> = ["zebra", "apple", "mango"]
+        .iter()
+        .map(|key| lua.find(&format!("[\"{}\"]", key)).unwrap())
+        .collect();
+    assert!(positions.windows(2).all(|w| w[0] < w[1]));
+
+    let list = stdout(&project.run(&["list"]).await);
+    assert!(list.find("zebra").unwrap() < list.find("apple").unwrap());
+}